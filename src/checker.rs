@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use crate::types::{Error, Result};
+
+pub type CheckFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// A probe for a URL scheme waitup has no built-in support for.
+///
+/// `check` gets the target as written, without its `#tags`, and the
+/// per-attempt timeout. Errors are treated like those of built-in probes:
+/// `Error::Config` stops waiting, anything else is retried.
+pub trait AsyncTargetChecker: Send + Sync {
+    fn check<'a>(&'a self, target: &'a str, timeout: Duration) -> CheckFuture<'a>;
+}
+
+#[derive(Clone)]
+pub struct Checker(Arc<dyn AsyncTargetChecker>);
+
+impl Checker {
+    pub fn check<'a>(&'a self, target: &'a str, timeout: Duration) -> CheckFuture<'a> {
+        self.0.check(target, timeout)
+    }
+}
+
+// Targets compare equal when they were parsed against the same registration.
+impl PartialEq for Checker {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Checker {}
+
+impl fmt::Debug for Checker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Checker(..)")
+    }
+}
+
+const BUILTIN_SCHEMES: &[&str] = &[
+    "consul", "etcd", "file", "free", "http", "https", "ldap", "ldaps", "mount", "nats", "npipe",
+    "pidfile", "process", "s3", "tcp", "tls", "udp", "vault", "zk",
+];
+
+static REGISTRY: RwLock<BTreeMap<&'static str, Checker>> = RwLock::new(BTreeMap::new());
+
+/// Routes `scheme://...` targets to `checker`, for `Target::parse` and for a
+/// CLI started with `waitup::run()` afterwards. Registering a scheme again
+/// replaces its checker for targets parsed from then on.
+pub fn register_checker<C>(scheme: &'static str, checker: C) -> Result<()>
+where
+    C: AsyncTargetChecker + 'static,
+{
+    let valid = scheme.starts_with(|c: char| c.is_ascii_lowercase())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '.'));
+    if !valid {
+        return Err(Error::Config(format!(
+            "Invalid scheme '{scheme}': expected lowercase letters, digits, '-' or '.'"
+        )));
+    }
+    if BUILTIN_SCHEMES.contains(&scheme) {
+        return Err(Error::Config(format!(
+            "Scheme '{scheme}' is built in and cannot be registered"
+        )));
+    }
+    REGISTRY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(scheme, Checker(Arc::new(checker)));
+    Ok(())
+}
+
+pub fn lookup(target_str: &str) -> Option<(&'static str, Checker)> {
+    let (scheme, _) = target_str.split_once("://")?;
+    REGISTRY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_key_value(scheme)
+        .map(|(scheme, checker)| (*scheme, checker.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::types::{Target, TargetKind, TargetOptions};
+
+    struct Flaky {
+        calls: AtomicU32,
+    }
+
+    impl AsyncTargetChecker for Flaky {
+        fn check<'a>(&'a self, target: &'a str, _timeout: Duration) -> CheckFuture<'a> {
+            Box::pin(async move {
+                assert_eq!(target, "flaky://queue/jobs");
+                match self.calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(Error::Connection("not yet".to_string())),
+                    _ => Ok(()),
+                }
+            })
+        }
+    }
+
+    struct Never;
+
+    impl AsyncTargetChecker for Never {
+        fn check<'a>(&'a self, _target: &'a str, _timeout: Duration) -> CheckFuture<'a> {
+            Box::pin(async { Err(Error::Connection("never".to_string())) })
+        }
+    }
+
+    #[test]
+    fn rejects_builtin_and_malformed_schemes() {
+        for scheme in ["http", "zk", "", "My", "a+b", "9p", "a:b"] {
+            assert!(matches!(
+                register_checker(scheme, Never),
+                Err(Error::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn registered_scheme_parses_with_tags() {
+        let options = TargetOptions::default();
+        assert!(Target::parse("unregistered://x", &options).is_err());
+        register_checker("parsed", Never).unwrap();
+        let target = Target::parse("parsed://host/path#tier=core", &options).unwrap();
+        assert!(matches!(target.kind, TargetKind::Custom { .. }));
+        assert_eq!(target.kind.name(), "parsed");
+        assert_eq!(target.to_string(), "parsed://host/path");
+        assert_eq!(target.tags, [("tier".to_string(), "core".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn custom_checker_is_retried_until_ready() {
+        register_checker(
+            "flaky",
+            Flaky {
+                calls: AtomicU32::new(0),
+            },
+        )
+        .unwrap();
+        let result = crate::wait(["flaky://queue/jobs"], "5s").await.unwrap();
+        assert_eq!(result.targets[0].attempts, 2);
+    }
+}
@@ -1,22 +1,63 @@
-use std::process::Command;
-use std::time::Duration;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use reqwest::Url;
+use tokio::time::Instant;
 
-use crate::connection::wait_for_targets;
-use crate::types::{Error, Headers, Result, Target, WaitConfig};
+use crate::connection::{http_client, wait_for_targets};
+use crate::dns;
+use crate::human;
+use crate::json::Value;
+use crate::log::{self, LogLevel};
+use crate::plan::{WaitPlan, into_result};
+use crate::presets::expand_target;
+use crate::profile::{DEFAULT_PROFILE, PartialWaitConfig, Preset, Profile, ProfileFile};
+use crate::report::{Report, SCHEMA};
+use crate::types::{
+    BearerToken, CircuitBreaker, DEFAULT_USER_AGENT, DnsFailure, DurationBounds, ERROR_CODES,
+    Error, ExpectedHeader, FileOptions, Headers, HttpOptions, LdapOptions, NatsOptions, ProbeLimit,
+    ProcessOptions, ProgressReporter, Quorum, Reporter, Result, RetryDecision, RetryHook,
+    S3Credentials, S3Options, Target, TargetFailure, TargetGroup, TargetOptions, TcpOptions,
+    TlsOptions, VaultOptions, WaitConfig, parse_duration, parse_tag,
+};
 
 #[derive(Parser)]
 #[command(name = "waitup")]
 #[command(about = "Block until host:port is reachable; exit non-zero on timeout")]
-#[command(version)]
+#[command(version, disable_version_flag = true)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(disable_help_subcommand = true, subcommand_value_name = "SUBCOMMAND")]
 struct Args {
+    #[command(subcommand)]
+    mode: Option<Mode>,
+
     #[arg(value_name = "TARGET")]
     targets: Vec<String>,
 
+    #[arg(long, env = "WAITUP_PRESET", value_enum)]
+    preset: Option<Preset>,
+
+    #[arg(long, env = "WAITUP_CONFIG", value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    #[arg(long, env = "WAITUP_PROFILE")]
+    profile: Option<String>,
+
     #[arg(short, long, env = "WAITUP_TIMEOUT", default_value = "30s")]
     timeout: String,
 
+    #[arg(long, env = "WAITUP_TARGET_TIMEOUT")]
+    target_timeout: Option<String>,
+
+    #[arg(long, env = "WAITUP_DEADLINE", value_name = "RFC3339")]
+    deadline: Option<String>,
+
     #[arg(short, long, env = "WAITUP_INTERVAL", default_value = "1s")]
     interval: String,
 
@@ -26,26 +67,365 @@ struct Args {
     #[arg(long, conflicts_with = "any")]
     all: bool,
 
+    #[arg(long)]
+    sequential: bool,
+
+    #[arg(long, env = "WAITUP_MAX_ATTEMPTS", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_attempts: Option<u32>,
+
+    #[arg(long, env = "WAITUP_INITIAL_DELAY", value_name = "DURATION")]
+    initial_delay: Option<String>,
+
+    #[arg(long, env = "WAITUP_SUCCESS_THRESHOLD", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    success_threshold: Option<u32>,
+
+    #[arg(long, env = "WAITUP_STABLE_FOR", value_name = "DURATION")]
+    stable_for: Option<String>,
+
+    #[arg(long, env = "WAITUP_MAX_CONCURRENCY", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_concurrency: Option<u32>,
+
+    #[arg(long)]
+    auto_http: bool,
+
     #[arg(long, action = clap::ArgAction::Append)]
     header: Vec<String>,
 
+    #[arg(long, env = "WAITUP_HEADERS_FILE", value_name = "FILE")]
+    headers_file: Option<PathBuf>,
+
+    #[arg(long, value_name = "VAR", conflicts_with = "bearer_token_file")]
+    bearer_token_env: Option<String>,
+
+    #[arg(long, value_name = "FILE")]
+    bearer_token_file: Option<PathBuf>,
+
+    #[arg(long, env = "WAITUP_USER_AGENT", default_value = DEFAULT_USER_AGENT)]
+    user_agent: String,
+
+    #[arg(long, value_name = "NAME[:VALUE]", action = clap::ArgAction::Append)]
+    expect_header: Vec<String>,
+
+    #[arg(long, value_name = "DURATION")]
+    max_response_time: Option<String>,
+
+    #[arg(long, value_name = "QUERY")]
+    graphql_query: Option<String>,
+
+    #[arg(long, value_name = "DAYS")]
+    cert_min_days: Option<u64>,
+
+    #[arg(long, value_name = "NAME")]
+    tls_server_name: Option<String>,
+
+    #[arg(long, value_name = "PROTOCOL")]
+    alpn: Option<String>,
+
+    #[arg(long)]
+    non_empty_body: bool,
+
+    #[arg(long, value_name = "BYTES")]
+    min_content_length: Option<usize>,
+
+    #[arg(long, value_name = "FILE")]
+    expect_json_file: Option<PathBuf>,
+
+    #[arg(long, value_name = "HEX")]
+    expect_sha256: Option<String>,
+
+    #[arg(long)]
+    head_first: bool,
+
+    #[arg(long, value_name = "PATH")]
+    unix_socket: Option<PathBuf>,
+
+    #[arg(long)]
+    file_non_empty: bool,
+
+    #[arg(long, value_name = "TEXT")]
+    file_contains: Option<String>,
+
+    #[arg(long, value_name = "DURATION")]
+    process_min_age: Option<String>,
+
+    #[arg(long)]
+    vault_standby_ok: bool,
+
+    #[arg(long)]
+    vault_sealed_ok: bool,
+
+    #[arg(long, env = "AWS_ENDPOINT_URL", value_name = "URL")]
+    s3_endpoint: Option<String>,
+
+    #[arg(long, env = "AWS_REGION", value_name = "REGION")]
+    s3_region: Option<String>,
+
+    #[arg(
+        long,
+        env = "AWS_ACCESS_KEY_ID",
+        value_name = "KEY",
+        hide_env_values = true,
+        requires = "s3_secret_key"
+    )]
+    s3_access_key: Option<String>,
+
+    #[arg(
+        long,
+        env = "AWS_SECRET_ACCESS_KEY",
+        value_name = "SECRET",
+        hide_env_values = true,
+        requires = "s3_access_key"
+    )]
+    s3_secret_key: Option<String>,
+
+    #[arg(
+        long,
+        env = "AWS_SESSION_TOKEN",
+        value_name = "TOKEN",
+        hide_env_values = true
+    )]
+    s3_session_token: Option<String>,
+
+    #[arg(long)]
+    nats_ping: bool,
+
+    #[arg(long, value_name = "DN")]
+    ldap_bind_dn: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAITUP_LDAP_PASSWORD",
+        value_name = "PASSWORD",
+        hide_env_values = true,
+        requires = "ldap_bind_dn"
+    )]
+    ldap_password: Option<String>,
+
+    #[cfg(feature = "consul")]
+    #[arg(long, env = "CONSUL_HTTP_ADDR", value_name = "URL")]
+    consul_addr: Option<String>,
+
+    #[cfg(feature = "consul")]
+    #[arg(long, env = "CONSUL_HTTP_TOKEN", hide_env_values = true)]
+    consul_token: Option<String>,
+
+    #[cfg(feature = "consul")]
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    consul_min_healthy: u32,
+
     #[arg(long, default_value = "10s")]
     connection_timeout: String,
 
+    #[arg(long, env = "WAITUP_BIND_ADDRESS", value_name = "IP")]
+    bind_address: Option<IpAddr>,
+
+    #[arg(long, env = "WAITUP_INTERFACE", value_name = "NAME")]
+    interface: Option<String>,
+
+    #[arg(long, env = "WAITUP_VIA", value_name = "USER@HOST")]
+    via: Option<String>,
+
+    #[arg(long)]
+    tcp_nodelay: bool,
+
+    #[arg(long, value_name = "DURATION")]
+    tcp_keepalive: Option<String>,
+
+    #[arg(long, value_name = "DURATION")]
+    tcp_linger: Option<String>,
+
+    #[arg(long)]
+    adaptive_timeout: bool,
+
+    #[arg(long)]
+    race_addresses: bool,
+
+    #[arg(long, value_name = "FAILURES")]
+    circuit_breaker: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "30s",
+        requires = "circuit_breaker"
+    )]
+    circuit_cooldown: String,
+
+    #[arg(long, env = "WAITUP_DNS_FAILURE", value_enum, default_value_t)]
+    dns_failure: DnsFailure,
+
+    #[arg(long, value_name = "DURATION")]
+    dns_interval: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAITUP_DNS_SERVER",
+        value_name = "IP[:PORT]",
+        value_delimiter = ',',
+        action = clap::ArgAction::Append
+    )]
+    dns_server: Vec<String>,
+
+    #[arg(long, value_name = "CODE", action = clap::ArgAction::Append)]
+    retry_on: Vec<String>,
+
+    #[arg(long, value_name = "CODE", action = clap::ArgAction::Append)]
+    abort_on: Vec<String>,
+
+    #[arg(long, value_name = "KEY=VALUE", action = clap::ArgAction::Append)]
+    select: Vec<String>,
+
+    #[arg(long, value_name = "TARGET,...", action = clap::ArgAction::Append)]
+    stage: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "NAME=TARGET,...",
+        action = clap::ArgAction::Append,
+        conflicts_with = "stage"
+    )]
+    group: Vec<String>,
+
+    #[arg(short, long)]
+    quiet: bool,
+
+    #[arg(long, env = "WAITUP_LOG_LEVEL", value_enum, value_name = "LEVEL")]
+    log_level: Option<LogLevel>,
+
+    #[arg(long)]
+    dry_run: bool,
+
+    #[arg(long)]
+    json: bool,
+
+    #[arg(long, env = "WAITUP_OUTPUT_FILE", value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    #[arg(long)]
+    json_schema: bool,
+
+    #[arg(long, value_name = "N", conflicts_with = "command")]
+    count: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "30s",
+        requires = "count"
+    )]
+    every: String,
+
+    #[arg(
+        long,
+        env = "WAITUP_COMMAND_RETRIES",
+        value_name = "N",
+        default_value_t = 0
+    )]
+    command_retries: u32,
+
+    #[arg(short = 'V', long, help = "Print version")]
+    version: bool,
+
     #[arg(last = true)]
     command: Vec<String>,
 }
 
+#[derive(Subcommand)]
+enum Mode {
+    Healthcheck(HealthcheckArgs),
+}
+
+#[derive(clap::Args)]
+struct HealthcheckArgs {
+    #[arg(value_name = "TARGET", required = true)]
+    targets: Vec<String>,
+
+    #[arg(short, long, default_value = "3s")]
+    timeout: String,
+
+    #[arg(long, value_name = "KEY:VALUE")]
+    header: Vec<String>,
+
+    #[arg(long, env = "WAITUP_HEADERS_FILE", value_name = "FILE")]
+    headers_file: Option<PathBuf>,
+}
+
 struct Config {
-    targets: Vec<Target>,
-    wait: WaitConfig,
+    plan: WaitPlan,
     command: Vec<String>,
+    command_retries: u32,
+    // First delay between command retries; doubles up to MAX_COMMAND_BACKOFF,
+    // which also caps the first one.
+    command_backoff: Duration,
+    dry_run: bool,
+    json: bool,
+    output_file: Option<PathBuf>,
+    quiet: bool,
+    repeat: Option<Repeat>,
+}
+
+struct Repeat {
+    // 0 repeats until interrupted
+    count: u32,
+    every: Duration,
+    timeout: Duration,
+    deadline: Option<Instant>,
+    config_file: Option<PathBuf>,
 }
 
-fn parse_duration(s: &str, label: &str) -> Result<Duration> {
-    s.parse::<humantime::Duration>()
-        .map(Into::into)
-        .map_err(|e| Error::Config(format!("Invalid {label} '{s}': {e}")))
+fn read_expected_json(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Cannot read JSON file '{}': {e}", path.display())))?;
+    let value = Value::parse(&content)
+        .ok_or_else(|| Error::Config(format!("Invalid JSON in '{}'", path.display())))?;
+    Ok(value.canonical().to_string())
+}
+
+fn parse_deadline(s: &str) -> Result<Instant> {
+    let at = humantime::parse_rfc3339_weak(s)
+        .map_err(|e| Error::Config(format!("Invalid deadline '{s}': {e}")))?;
+    let remaining = at
+        .duration_since(SystemTime::now())
+        .map_err(|_| Error::Config(format!("Deadline '{s}' is in the past")))?;
+    Ok(Instant::now() + remaining)
+}
+
+fn parse_error_codes(raw: &[String]) -> Result<Vec<String>> {
+    raw.iter()
+        .map(|code| {
+            let code = code.trim().to_ascii_uppercase();
+            if ERROR_CODES.contains(&code.as_str()) {
+                Ok(code)
+            } else {
+                Err(Error::Config(format!(
+                    "Unknown error code '{code}', expected one of: {}",
+                    ERROR_CODES.join(", ")
+                )))
+            }
+        })
+        .collect()
+}
+
+fn retry_hook(
+    retry_on: &[String],
+    abort_on: &[String],
+    dns_failure: DnsFailure,
+) -> Result<Option<RetryHook>> {
+    let retry_on = parse_error_codes(retry_on)?;
+    let abort_on = parse_error_codes(abort_on)?;
+    if retry_on.is_empty() && abort_on.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(RetryHook::new(move |error| {
+        let code = error.code();
+        if abort_on.iter().any(|c| c == code) {
+            RetryDecision::Abort
+        } else if retry_on.iter().any(|c| c == code) {
+            RetryDecision::Retry
+        } else {
+            RetryDecision::classify(error, dns_failure)
+        }
+    })))
 }
 
 fn parse_headers(raw: &[String]) -> Result<Headers> {
@@ -59,57 +439,829 @@ fn parse_headers(raw: &[String]) -> Result<Headers> {
         .collect()
 }
 
+fn expand_env(text: &str) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::Config(format!("Unterminated '${{' in '{text}'")))?;
+        let name = &rest[start + 2..start + end];
+        let value = std::env::var(name)
+            .map_err(|_| Error::Config(format!("Environment variable '{name}' is not set")))?;
+        out.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// One `Key: value` per line; blank lines and `#` comments are skipped.
+// Values are expanded after splitting so errors never echo a secret.
+fn read_headers_file(path: &Path) -> Result<Headers> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        Error::Config(format!(
+            "Cannot read headers file '{}': {e}",
+            path.display()
+        ))
+    })?;
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (key, value) = line.split_once(':').ok_or_else(|| {
+                Error::Config(format!(
+                    "Invalid header format '{line}' in '{}': expected 'key:value'",
+                    path.display()
+                ))
+            })?;
+            let value = expand_env(value.trim())
+                .map_err(|e| Error::Config(format!("{}: {e}", path.display())))?;
+            Ok((key.trim().to_string(), value))
+        })
+        .collect()
+}
+
+fn load_headers(raw: &[String], file: Option<&Path>) -> Result<Headers> {
+    let mut headers = file.map(read_headers_file).transpose()?.unwrap_or_default();
+    headers.extend(parse_headers(raw)?);
+    Ok(headers)
+}
+
+fn parse_expected_headers(raw: &[String]) -> Vec<ExpectedHeader> {
+    raw.iter()
+        .map(|h| match h.split_once(':') {
+            Some((key, value)) => (key.trim().to_string(), Some(value.trim().to_string())),
+            None => (h.trim().to_string(), None),
+        })
+        .collect()
+}
+
+fn profile_layer(profile: Profile, path: &Path) -> Result<PartialWaitConfig> {
+    let mut layer = PartialWaitConfig::default();
+    for (key, value) in profile {
+        match key.as_str() {
+            "timeout" => layer.timeout = Some(value),
+            "target-timeout" => layer.target_timeout = Some(value),
+            "interval" => layer.interval = Some(value),
+            "connection-timeout" => layer.connection_timeout = Some(value),
+            "user-agent" => layer.user_agent = Some(value),
+            "targets" => layer.targets = Some(split_stage(&value)),
+            _ => {
+                return Err(Error::Config(format!(
+                    "Unknown profile setting '{key}' in '{}'",
+                    path.display()
+                )));
+            }
+        }
+    }
+    Ok(layer)
+}
+
+fn args_layer(args: &Args, matches: &ArgMatches, source: ValueSource) -> PartialWaitConfig {
+    let from = |id: &str| matches.value_source(id) == Some(source);
+    PartialWaitConfig {
+        timeout: from("timeout").then(|| args.timeout.clone()),
+        target_timeout: args
+            .target_timeout
+            .clone()
+            .filter(|_| from("target_timeout")),
+        interval: from("interval").then(|| args.interval.clone()),
+        connection_timeout: from("connection_timeout").then(|| args.connection_timeout.clone()),
+        user_agent: from("user_agent").then(|| args.user_agent.clone()),
+        targets: from("targets").then(|| args.targets.clone()),
+    }
+}
+
+// Lowest to highest precedence: built-in defaults (already in args), --preset,
+// the config file profile, WAITUP_* environment variables, command-line flags.
+fn layered_config(args: &Args, matches: &ArgMatches) -> Result<PartialWaitConfig> {
+    let mut config = args.preset.map(Preset::layer).unwrap_or_default();
+    if let Some(path) = &args.config {
+        let file = ProfileFile::from_file(path)?;
+        let profile = match args.profile.as_deref() {
+            Some(name) => file.profile(name)?,
+            None if file.has_profile(DEFAULT_PROFILE) => file.profile(DEFAULT_PROFILE)?,
+            None => Profile::new(),
+        };
+        config = config.merge(profile_layer(profile, path)?);
+    } else if matches.value_source("profile") == Some(ValueSource::CommandLine) {
+        // An exported WAITUP_PROFILE alone is ignored: there is nothing to select from.
+        return Err(Error::Config(
+            "--profile needs a config file (--config or WAITUP_CONFIG)".to_string(),
+        ));
+    }
+    Ok(config
+        .merge(args_layer(args, matches, ValueSource::EnvVariable))
+        .merge(args_layer(args, matches, ValueSource::CommandLine)))
+}
+
+fn apply_layer(args: &mut Args, layer: PartialWaitConfig) {
+    if let Some(timeout) = layer.timeout {
+        args.timeout = timeout;
+    }
+    if let Some(target_timeout) = layer.target_timeout {
+        args.target_timeout = Some(target_timeout);
+    }
+    if let Some(interval) = layer.interval {
+        args.interval = interval;
+    }
+    if let Some(connection_timeout) = layer.connection_timeout {
+        args.connection_timeout = connection_timeout;
+    }
+    if let Some(user_agent) = layer.user_agent {
+        args.user_agent = user_agent;
+    }
+    if let Some(targets) = layer.targets {
+        args.targets = targets;
+    }
+}
+
+fn split_stage(raw: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match targets.last_mut() {
+            Some(last) if !part.contains(':') => {
+                last.push(',');
+                last.push_str(part);
+            }
+            _ => targets.push(part.to_string()),
+        }
+    }
+    targets
+}
+
+// NAME[:QUORUM]=TARGET,... where QUORUM is all (the default), any or a count.
+fn parse_group(raw: &str) -> Result<(String, Quorum, Vec<String>)> {
+    let (head, targets) = raw.split_once('=').ok_or_else(|| {
+        Error::Config(format!(
+            "Invalid group '{raw}': expected NAME[:QUORUM]=TARGET,..."
+        ))
+    })?;
+    let (name, quorum) = match head.split_once(':') {
+        Some((name, quorum)) => (name.trim(), quorum.trim().parse()?),
+        None => (head.trim(), Quorum::AllOf),
+    };
+    if name.is_empty() {
+        return Err(Error::Config(format!(
+            "Invalid group '{raw}': missing name"
+        )));
+    }
+    Ok((name.to_string(), quorum, split_stage(targets)))
+}
+
 fn build_config(args: Args) -> Result<Config> {
-    if args.targets.is_empty() {
+    if args.targets.is_empty() && args.stage.is_empty() && args.group.is_empty() {
         return Err(Error::Config(
             "At least one target must be specified".to_string(),
         ));
     }
 
-    let headers = parse_headers(&args.header)?;
-    let targets: Vec<Target> = args
-        .targets
+    let cert_min_validity = args
+        .cert_min_days
+        .map(|days| {
+            days.checked_mul(86_400)
+                .map(Duration::from_secs)
+                .ok_or_else(|| {
+                    Error::Config(format!("Invalid cert-min-days '{days}': too many days"))
+                })
+        })
+        .transpose()?;
+    let http = HttpOptions {
+        headers: load_headers(&args.header, args.headers_file.as_deref())?,
+        expect_headers: parse_expected_headers(&args.expect_header),
+        max_response_time: args
+            .max_response_time
+            .as_deref()
+            .map(|t| parse_duration(t, "max-response-time", DurationBounds::TIMEOUT))
+            .transpose()?,
+        min_content_length: match (args.min_content_length, args.non_empty_body) {
+            (Some(min), non_empty) => Some(min.max(usize::from(non_empty))),
+            (None, true) => Some(1),
+            (None, false) => None,
+        },
+        expect_json: args
+            .expect_json_file
+            .as_deref()
+            .map(read_expected_json)
+            .transpose()?,
+        expect_sha256: args
+            .expect_sha256
+            .map(|digest| {
+                if digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                    Ok(digest.to_ascii_lowercase())
+                } else {
+                    Err(Error::Config(format!(
+                        "Invalid SHA-256 digest '{digest}': expected 64 hex characters"
+                    )))
+                }
+            })
+            .transpose()?,
+        head_first: args.head_first,
+        unix_socket: args.unix_socket,
+        basic_auth: None,
+        bearer_token: match (args.bearer_token_env, args.bearer_token_file) {
+            (Some(name), _) => Some(BearerToken::Env(name)),
+            (None, Some(path)) => Some(BearerToken::File(path)),
+            (None, None) => None,
+        },
+        graphql_query: args.graphql_query,
+        cert_min_validity,
+    };
+    let tcp = TcpOptions {
+        nodelay: args.tcp_nodelay,
+        keepalive: args
+            .tcp_keepalive
+            .as_deref()
+            .map(|t| parse_duration(t, "tcp-keepalive", DurationBounds::INTERVAL))
+            .transpose()?,
+        linger: args
+            .tcp_linger
+            .as_deref()
+            .map(|t| parse_duration(t, "tcp-linger", DurationBounds::LINGER))
+            .transpose()?,
+    };
+    let options = TargetOptions {
+        auto_http: args.auto_http,
+        tcp,
+        http,
+        tls: TlsOptions {
+            starttls: None,
+            server_name: args.tls_server_name,
+            alpn: args.alpn,
+            cert_min_validity,
+        },
+        file: FileOptions {
+            non_empty: args.file_non_empty,
+            contains: args.file_contains.filter(|text| !text.is_empty()),
+        },
+        process: ProcessOptions {
+            min_age: args
+                .process_min_age
+                .as_deref()
+                .map(|t| parse_duration(t, "process-min-age", DurationBounds::TIMEOUT))
+                .transpose()?,
+        },
+        s3: S3Options {
+            endpoint: args
+                .s3_endpoint
+                .as_deref()
+                .map(|endpoint| {
+                    Url::parse(endpoint).map_err(|e| {
+                        Error::Config(format!("Invalid S3 endpoint '{endpoint}': {e}"))
+                    })
+                })
+                .transpose()?,
+            region: args.s3_region,
+            credentials: args.s3_access_key.zip(args.s3_secret_key).map(
+                |(access_key, secret_key)| S3Credentials {
+                    access_key,
+                    secret_key,
+                    session_token: args.s3_session_token,
+                },
+            ),
+        },
+        nats: NatsOptions {
+            ping: args.nats_ping,
+        },
+        ldap: LdapOptions {
+            bind_dn: args.ldap_bind_dn,
+            password: args.ldap_password,
+        },
+        vault: VaultOptions {
+            standby_ok: args.vault_standby_ok,
+            sealed_ok: args.vault_sealed_ok,
+        },
+        #[cfg(feature = "consul")]
+        consul: crate::types::ConsulOptions {
+            addr: args
+                .consul_addr
+                .as_deref()
+                .map(crate::consul::parse_addr)
+                .transpose()?,
+            token: args.consul_token,
+            min_healthy: args.consul_min_healthy,
+        },
+    };
+    let selectors = args
+        .select
         .iter()
-        .map(|s| Target::parse(s, &headers))
-        .collect::<Result<_>>()?;
-    let wait_for_any = args.any || (!args.all && targets.len() == 1);
+        .map(|s| parse_tag(s))
+        .collect::<Result<Vec<_>>>()?;
+    let mut stages = Vec::new();
+    if !args.targets.is_empty() {
+        stages.push(args.targets.clone());
+    }
+    stages.extend(args.stage.iter().map(|s| split_stage(s)));
+
+    let parse_targets = |specs: &[String]| {
+        specs
+            .iter()
+            .map(|s| expand_target(s))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .map(|s| Target::parse(&s, &options))
+            .filter(|t| t.as_ref().map_or(true, |t| t.matches(&selectors)))
+            .collect::<Result<Vec<_>>>()
+    };
+    let mut stages = stages
+        .iter()
+        .map(|stage| {
+            let mut group: TargetGroup = parse_targets(stage)?.into_iter().collect();
+            if args.any || (!args.all && group.targets.len() == 1) {
+                group.quorum = Quorum::AnyOf;
+            }
+            Ok(vec![group])
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Groups are checked alongside the positional targets.
+    for raw in &args.group {
+        let (name, quorum, specs) = parse_group(raw)?;
+        let targets = parse_targets(&specs)?;
+        if let Quorum::KOfN(k) = quorum
+            && !targets.is_empty()
+            && k > targets.len()
+        {
+            return Err(Error::Config(format!(
+                "Group '{name}' needs {k} ready targets but only has {}",
+                targets.len()
+            )));
+        }
+        if stages.is_empty() {
+            stages.push(Vec::new());
+        }
+        stages[0].push(TargetGroup {
+            targets: targets.into_iter().map(Arc::new).collect(),
+            ..TargetGroup::new(name, quorum)
+        });
+    }
+    for groups in &mut stages {
+        groups.retain(|group| !group.targets.is_empty());
+    }
+
+    if stages.iter().all(Vec::is_empty) {
+        return Err(Error::Config(
+            "No targets match the --select filters".to_string(),
+        ));
+    }
+
+    if args.interface.is_some()
+        && !cfg!(any(
+            target_os = "android",
+            target_os = "fuchsia",
+            target_os = "linux"
+        ))
+    {
+        return Err(Error::Config(
+            "--interface is only supported on Linux".to_string(),
+        ));
+    }
+
+    let overall_timeout = parse_duration(&args.timeout, "timeout", DurationBounds::TIMEOUT)?;
+    let budget_end = Instant::now() + overall_timeout;
+    let deadline = args.deadline.as_deref().map(parse_deadline).transpose()?;
+    let repeat = args
+        .count
+        .map(|count| -> Result<Repeat> {
+            Ok(Repeat {
+                count,
+                every: parse_duration(&args.every, "every", DurationBounds::INTERVAL)?,
+                timeout: overall_timeout,
+                deadline,
+                config_file: args.config.clone(),
+            })
+        })
+        .transpose()?;
+    let mut wait = WaitConfig {
+        overall_timeout,
+        target_timeout: args
+            .target_timeout
+            .as_deref()
+            .map(|t| parse_duration(t, "target-timeout", DurationBounds::TIMEOUT))
+            .transpose()?,
+        initial_interval: parse_duration(&args.interval, "interval", DurationBounds::INTERVAL)?,
+        quorum: Quorum::AllOf,
+        sequential: args.sequential,
+        max_attempts: args.max_attempts,
+        connection_timeout: parse_duration(
+            &args.connection_timeout,
+            "connection-timeout",
+            DurationBounds::TIMEOUT,
+        )?,
+        deadline: Some(deadline.map_or(budget_end, |d| d.min(budget_end))),
+        reporter: (!args.quiet).then(|| Reporter::new(StatusLines)),
+        adaptive_timeout: args.adaptive_timeout,
+        race_addresses: args.race_addresses,
+        probe_limit: args.max_concurrency.map(ProbeLimit::new),
+        initial_delay: args
+            .initial_delay
+            .as_deref()
+            .map(|t| parse_initial_delay(t, overall_timeout))
+            .transpose()?,
+        success_threshold: args.success_threshold.unwrap_or(1),
+        stable_for: args
+            .stable_for
+            .as_deref()
+            .map(|t| parse_duration(t, "stable-for", DurationBounds::TIMEOUT))
+            .transpose()?,
+        dns_failure: args.dns_failure,
+        circuit_breaker: args
+            .circuit_breaker
+            .map(|threshold| -> Result<CircuitBreaker> {
+                if threshold == 0 {
+                    return Err(Error::Config(
+                        "circuit-breaker must be at least 1".to_string(),
+                    ));
+                }
+                Ok(CircuitBreaker {
+                    threshold,
+                    cooldown: parse_duration(
+                        &args.circuit_cooldown,
+                        "circuit-cooldown",
+                        DurationBounds::INTERVAL,
+                    )?,
+                })
+            })
+            .transpose()?,
+        retry_hook: retry_hook(&args.retry_on, &args.abort_on, args.dns_failure)?,
+        dns_interval: args
+            .dns_interval
+            .as_deref()
+            .map(|t| parse_duration(t, "dns-interval", DurationBounds::INTERVAL))
+            .transpose()?,
+        dns_servers: args
+            .dns_server
+            .iter()
+            .map(|s| dns::parse_server(s))
+            .collect::<Result<_>>()?,
+        user_agent: args.user_agent,
+        bind_address: args.bind_address,
+        interface: args.interface,
+        via: args.via,
+        http_client: None,
+        pause: None,
+    };
+    wait.http_client = Some(http_client(&wait, cert_min_validity.is_some())?);
+
+    let staged = stages.len() > 1;
+    let mut plan = WaitPlan::new();
+    for (index, groups) in stages.into_iter().enumerate() {
+        if groups.is_empty() {
+            continue;
+        }
+        let name = if staged {
+            format!("stage {}", index + 1)
+        } else {
+            "targets".to_string()
+        };
+        let config = WaitConfig {
+            // Later stages start right after the previous one is ready.
+            initial_delay: wait.initial_delay.filter(|_| plan.phases().is_empty()),
+            ..wait.clone()
+        };
+        plan = plan.phase(name, groups, config);
+    }
 
     Ok(Config {
-        targets,
-        wait: WaitConfig {
-            timeout: parse_duration(&args.timeout, "timeout")?,
-            initial_interval: parse_duration(&args.interval, "interval")?,
-            wait_for_any,
-            connection_timeout: parse_duration(&args.connection_timeout, "connection-timeout")?,
-        },
+        command_backoff: wait.initial_interval,
+        plan,
         command: args.command,
+        command_retries: args.command_retries,
+        dry_run: args.dry_run,
+        json: args.json,
+        output_file: args.output_file,
+        quiet: args.quiet,
+        repeat,
     })
 }
 
-fn execute_command(command: &[String]) -> Result<()> {
-    if command.is_empty() {
-        return Ok(());
+fn print_plan(config: &Config) {
+    let fmt = humantime::format_duration;
+    println!("Dry run: no connections will be made");
+    for phase in config.plan.phases() {
+        let wait = &phase.config;
+        let grouped = phase.groups.len() > 1;
+        println!(
+            "Phase '{}': wait for {}, {}",
+            phase.name,
+            match phase.groups.as_slice() {
+                [group] => group.quorum.to_string(),
+                groups => format!("{} groups", groups.len()),
+            },
+            if wait.sequential {
+                "sequentially"
+            } else {
+                "concurrently"
+            }
+        );
+        println!(
+            "  timeout {} (shared by all phases), connection timeout {}{}",
+            fmt(wait.overall_timeout),
+            fmt(wait.connection_timeout),
+            if wait.adaptive_timeout {
+                " (adaptive)"
+            } else {
+                ""
+            }
+        );
+        if wait.race_addresses {
+            println!("  all resolved addresses raced, first to connect wins");
+        }
+        if let Some(target_timeout) = wait.target_timeout {
+            println!("  per-target timeout {}", fmt(target_timeout));
+        }
+        if let Some(max) = wait.max_attempts {
+            println!("  at most {max} attempts per target");
+        }
+        if let Some(delay) = wait.initial_delay {
+            println!("  first probe after {}", fmt(delay));
+        }
+        if wait.success_threshold > 1 {
+            println!(
+                "  ready after {} consecutive successful probes",
+                wait.success_threshold
+            );
+        }
+        if let Some(stable_for) = wait.stable_for {
+            println!("  ready once a target stays up for {}", fmt(stable_for));
+        }
+        if let Some(limit) = &wait.probe_limit {
+            println!("  at most {} probes in flight", limit.max);
+        }
+        match (&wait.bind_address, &wait.interface) {
+            (Some(ip), Some(interface)) => println!("  probes bound to {ip} on {interface}"),
+            (Some(ip), None) => println!("  probes bound to {ip}"),
+            (None, Some(interface)) => println!("  probes bound to {interface}"),
+            (None, None) => {}
+        }
+        if let Some(jump) = &wait.via {
+            println!("  probes run on {jump} over ssh");
+        }
+        println!(
+            "  backoff: fixed interval of {}",
+            fmt(wait.initial_interval)
+        );
+        match (wait.dns_failure, wait.dns_interval) {
+            (DnsFailure::Fail, _) => println!("  DNS failures are fatal"),
+            (DnsFailure::Retry, Some(interval)) => {
+                println!("  DNS failures retried every {}", fmt(interval));
+            }
+            (DnsFailure::Retry, None) => {}
+        }
+        if !wait.dns_servers.is_empty() {
+            let servers: Vec<String> = wait.dns_servers.iter().map(ToString::to_string).collect();
+            println!("  names resolved via {}", servers.join(", "));
+        }
+        if let Some(breaker) = wait.circuit_breaker {
+            println!(
+                "  circuit breaker: cool down {} after {} identical failures",
+                fmt(breaker.cooldown),
+                breaker.threshold
+            );
+        }
+        for group in &phase.groups {
+            let indent = if grouped {
+                println!(
+                    "  group '{}': wait for {} of {}",
+                    group.name,
+                    group.quorum,
+                    group.targets.len()
+                );
+                "    "
+            } else {
+                "  "
+            };
+            for target in &group.targets {
+                let tags: Vec<String> = target
+                    .tags
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect();
+                if tags.is_empty() {
+                    println!("{indent}- {target}");
+                } else {
+                    println!("{indent}- {target} [{}]", tags.join(", "));
+                }
+            }
+        }
+    }
+    if let Some(path) = &config.output_file {
+        println!("JSON report written to {}", path.display());
     }
+    if !config.command.is_empty() {
+        println!("Then run: {}", config.command.join(" "));
+        if config.command_retries > 0 {
+            println!(
+                "  retried up to {} times on non-zero exit, backoff starting at {}",
+                config.command_retries,
+                humantime::format_duration(config.command_backoff)
+            );
+        }
+    }
+    if let Some(repeat) = &config.repeat {
+        let every = humantime::format_duration(repeat.every);
+        match repeat.count {
+            0 => println!("Repeat every {every} until interrupted"),
+            count => println!("Repeat {count} times, every {every}"),
+        }
+    }
+}
+
+const MAX_COMMAND_BACKOFF: Duration = Duration::from_secs(30);
 
-    let status = Command::new(&command[0])
+fn execute_command(command: &[String]) -> Result<ExitStatus> {
+    Command::new(&command[0])
         .args(&command[1..])
         .status()
-        .map_err(|e| Error::Command(e.to_string()))?;
+        .map_err(|e| Error::Command(e.to_string()))
+}
 
-    if !status.success() {
-        return Err(Error::Command(format!(
-            "Exited with code: {:?}",
-            status.code()
+// A delay that uses up the whole timeout would never probe.
+fn parse_initial_delay(raw: &str, timeout: Duration) -> Result<Duration> {
+    let delay = parse_duration(raw, "initial-delay", DurationBounds::TIMEOUT)?;
+    if delay >= timeout {
+        return Err(Error::Config(format!(
+            "Invalid initial-delay '{raw}': must be shorter than the timeout ({})",
+            human::duration(timeout)
         )));
     }
+    Ok(delay)
+}
 
-    Ok(())
+// Only a non-zero exit is retried; a command that cannot be started fails at once.
+async fn run_command(config: &Config) -> Result<()> {
+    if config.command.is_empty() {
+        return Ok(());
+    }
+
+    let mut delay = config.command_backoff.min(MAX_COMMAND_BACKOFF);
+    let mut retries = 0;
+    loop {
+        let status = execute_command(&config.command)?;
+        if status.success() {
+            return Ok(());
+        }
+        if retries == config.command_retries {
+            return Err(Error::Command(format!(
+                "Exited with code: {:?}",
+                status.code()
+            )));
+        }
+        retries += 1;
+        if !config.quiet {
+            eprintln!(
+                "Command exited with code: {:?}, retry {retries}/{} in {}",
+                status.code(),
+                config.command_retries,
+                human::duration(delay)
+            );
+        }
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2).min(MAX_COMMAND_BACKOFF);
+    }
 }
 
-pub async fn run() -> i32 {
-    let args = Args::parse();
+fn parse_args() -> Result<Args> {
+    let matches = Args::command().try_get_matches().unwrap_or_else(|e| {
+        // Docker HEALTHCHECK only knows 0 (healthy) and 1 (unhealthy).
+        if e.use_stderr() && std::env::args().nth(1).as_deref() == Some("healthcheck") {
+            let _ = e.print();
+            std::process::exit(1);
+        }
+        e.exit()
+    });
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if args.mode.is_some() {
+        return Ok(args);
+    }
+    let layer = layered_config(&args, &matches)?;
+    apply_layer(&mut args, layer);
+    Ok(args)
+}
+
+async fn healthcheck(args: HealthcheckArgs) -> Result<()> {
+    let timeout = parse_duration(&args.timeout, "timeout", DurationBounds::TIMEOUT)?;
+    let options = TargetOptions {
+        http: HttpOptions {
+            headers: load_headers(&args.header, args.headers_file.as_deref())?,
+            ..HttpOptions::default()
+        },
+        ..TargetOptions::default()
+    };
+    let targets = args
+        .targets
+        .iter()
+        .map(|s| expand_target(s))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .map(|s| Target::parse(&s, &options))
+        .collect::<Result<Vec<_>>>()?;
+    let config = Arc::new(WaitConfig {
+        overall_timeout: timeout,
+        initial_interval: timeout,
+        max_attempts: Some(1),
+        connection_timeout: timeout,
+        dns_failure: DnsFailure::Fail,
+        deadline: Some(Instant::now() + timeout),
+        ..WaitConfig::default()
+    });
+    wait_for_targets(targets, &config)
+        .await
+        .error
+        .map_or(Ok(()), Err)
+}
 
-    let config = match build_config(args) {
+fn version_json() -> Value {
+    let mut protocols = vec![
+        "tcp",
+        "tls",
+        "starttls",
+        "http",
+        "https",
+        "graphql",
+        "file",
+        "mount",
+        "free",
+        "exec",
+        "etcd",
+        "vault",
+        "s3",
+        "ldap",
+        "ldaps",
+        "nats",
+        "zookeeper",
+    ];
+    if cfg!(target_os = "linux") {
+        protocols.push("process");
+    }
+    if cfg!(windows) {
+        protocols.push("npipe");
+    }
+    let mut features = Vec::new();
+    if cfg!(feature = "consul") {
+        features.push("consul");
+        protocols.push("consul");
+    }
+    if cfg!(feature = "server") {
+        features.push("server");
+    }
+    let string = |s: &str| Value::String(s.to_string());
+    let strings = |items: Vec<&str>| Value::Array(items.into_iter().map(string).collect());
+    Value::Object(vec![
+        ("name".into(), string(env!("CARGO_PKG_NAME"))),
+        ("version".into(), string(env!("CARGO_PKG_VERSION"))),
+        (
+            "target".into(),
+            Value::String(format!(
+                "{}-{}",
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            )),
+        ),
+        ("tls".into(), string("rustls")),
+        ("features".into(), strings(features)),
+        ("protocols".into(), strings(protocols)),
+    ])
+}
+
+pub async fn run() -> i32 {
+    let args = match parse_args() {
+        Ok(args) if args.version => {
+            if args.json {
+                println!("{}", version_json());
+            } else {
+                println!("waitup {}", env!("CARGO_PKG_VERSION"));
+            }
+            return 0;
+        }
+        Ok(args) if args.json_schema => {
+            println!("{SCHEMA}");
+            return 0;
+        }
+        Ok(Args {
+            mode: Some(Mode::Healthcheck(args)),
+            ..
+        }) => {
+            return match healthcheck(args).await {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    1
+                }
+            };
+        }
+        other => other,
+    };
+    let args = args.and_then(|args| {
+        log::init(args.log_level)?;
+        Ok(args)
+    });
+    let mut config = match args.and_then(build_config) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Error: {e}");
@@ -117,15 +1269,194 @@ pub async fn run() -> i32 {
         }
     };
 
-    if let Err(e) = wait_for_targets(&config.targets, &config.wait).await {
+    if config.dry_run {
+        print_plan(&config);
+        return 0;
+    }
+
+    if let Some(repeat) = config.repeat.take() {
+        return run_repeated(&mut config, repeat).await;
+    }
+
+    if let Err(e) = run_plan(&config).await {
         eprintln!("Error: {e}");
-        return 1;
+        return if e.is_config() { 2 } else { 1 };
     }
 
-    if let Err(e) = execute_command(&config.command) {
+    if let Err(e) = run_command(&config).await {
         eprintln!("Command error: {e}");
         return 3;
     }
 
     0
 }
+
+// One line per target as it finishes; silenced by --quiet.
+struct StatusLines;
+
+impl ProgressReporter for StatusLines {
+    fn target_finished(
+        &self,
+        target: &Target,
+        elapsed: Duration,
+        attempts: u32,
+        result: &Result<()>,
+    ) {
+        let (outcome, detail) = match result {
+            Ok(()) => ("ready", None),
+            Err(Error::Timeout { targets }) => {
+                ("timed out", targets.first().and_then(TargetFailure::reason))
+            }
+            Err(Error::Exhausted(failure)) => ("gave up on", failure.reason()),
+            Err(e) => ("failed", Some(e.to_string())),
+        };
+        let plural = if attempts == 1 { "" } else { "s" };
+        let line = format!(
+            "{outcome} {target} in {} after {attempts} attempt{plural}",
+            human::duration(elapsed)
+        );
+        match detail {
+            Some(e) => eprintln!("{line}: {e}"),
+            None => eprintln!("{line}"),
+        }
+    }
+}
+
+async fn run_plan(config: &Config) -> Result<()> {
+    let results = config.plan.run().await;
+    if results.len() > 1 && !config.quiet {
+        for phase in results.iter().filter(|p| p.result.is_success()) {
+            eprintln!(
+                "Phase '{}' ready in {}",
+                phase.name,
+                human::duration(phase.result.elapsed)
+            );
+        }
+    }
+    if config.json || config.output_file.is_some() {
+        let report = Report::from_phases(&results).to_json();
+        match &config.output_file {
+            Some(path) => fs::write(path, format!("{report}\n")).map_err(|e| {
+                Error::Config(format!("Cannot write report to {}: {e}", path.display()))
+            })?,
+            None => println!("{report}"),
+        }
+    }
+    into_result(results)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Re-reads argv and the --config file, so profile edits apply to the next run.
+fn reload(config: &mut Config, repeat: &mut Repeat) -> Result<()> {
+    let fresh = parse_args().and_then(build_config)?;
+    config.plan = fresh.plan;
+    if let Some(fresh) = fresh.repeat {
+        repeat.timeout = fresh.timeout;
+    }
+    Ok(())
+}
+
+async fn run_repeated(config: &mut Config, mut repeat: Repeat) -> i32 {
+    let total = match repeat.count {
+        0 => String::new(),
+        count => format!("/{count}"),
+    };
+    let mut failed = Vec::new();
+    let mut runs = 0;
+    let mut ready = 0;
+    let mut config_modified = repeat.config_file.as_deref().and_then(modified);
+    loop {
+        if let Some(path) = repeat.config_file.clone()
+            && modified(&path) != config_modified
+        {
+            config_modified = modified(&path);
+            match reload(config, &mut repeat) {
+                Ok(()) if !config.quiet => eprintln!("Reloaded {}", path.display()),
+                Ok(()) => {}
+                Err(e) => eprintln!("Error: {e}; keeping the previous config"),
+            }
+        }
+        runs += 1;
+        let started = Instant::now();
+        let budget_end = started + repeat.timeout;
+        config
+            .plan
+            .set_deadline(repeat.deadline.map_or(budget_end, |d| d.min(budget_end)));
+        match run_plan(config).await {
+            Ok(()) => {
+                ready += 1;
+                if !config.quiet {
+                    eprintln!(
+                        "Run {runs}{total}: ready in {}",
+                        human::duration(started.elapsed())
+                    );
+                }
+            }
+            Err(e) if e.is_config() => {
+                eprintln!("Error: {e}");
+                return 2;
+            }
+            Err(e) => {
+                eprintln!("Run {runs}{total}: {e}");
+                failed.push(runs.to_string());
+            }
+        }
+        if runs == repeat.count {
+            break;
+        }
+        tokio::time::sleep_until(started + repeat.every).await;
+    }
+
+    if failed.is_empty() {
+        if !config.quiet {
+            eprintln!("Rollup: {ready}/{runs} runs ready");
+        }
+        0
+    } else {
+        eprintln!(
+            "Rollup: {ready}/{runs} runs ready, failed: {}",
+            failed.join(", ")
+        );
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_delay_must_be_shorter_than_timeout() {
+        let timeout = Duration::from_secs(30);
+        assert_eq!(
+            parse_initial_delay("29s", timeout).unwrap(),
+            Duration::from_secs(29)
+        );
+        for raw in ["30s", "31s", "1h"] {
+            assert!(matches!(
+                parse_initial_delay(raw, timeout),
+                Err(Error::Config(_))
+            ));
+        }
+        assert!(parse_initial_delay("soon", timeout).is_err());
+    }
+
+    #[test]
+    fn version_json_lists_enabled_features() {
+        let expected: Vec<Value> = [
+            ("consul", cfg!(feature = "consul")),
+            ("server", cfg!(feature = "server")),
+        ]
+        .into_iter()
+        .filter(|&(_, enabled)| enabled)
+        .map(|(name, _)| Value::String(name.to_string()))
+        .collect();
+        assert_eq!(
+            version_json().get("features"),
+            Some(&Value::Array(expected))
+        );
+    }
+}
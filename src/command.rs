@@ -0,0 +1,139 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::thread::{self, JoinHandle, sleep};
+use std::time::{Duration, Instant};
+
+use crate::human;
+use crate::types::{Error, Result};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+// `input`, if any, is written to the child's stdin, which must be piped.
+pub fn run(
+    mut command: Command,
+    input: Option<Vec<u8>>,
+    limit: Duration,
+) -> io::Result<Option<Output>> {
+    let mut child = command.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // From a thread, so a child that never reads cannot stall the timeout.
+        thread::spawn(move || stdin.write_all(&input));
+    }
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() >= limit {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        sleep(POLL_INTERVAL);
+    };
+    Ok(Some(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars.next().ok_or_else(|| {
+                    Error::Config(format!("Trailing backslash in command '{line}'"))
+                })?;
+                word.get_or_insert_default().push(escaped);
+            }
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(Error::Config(format!(
+            "Unterminated quote in command '{line}'"
+        )));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+pub fn join_words(argv: &[String]) -> String {
+    argv.iter()
+        .map(|word| {
+            if !word.is_empty()
+                && !word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c))
+            {
+                word.clone()
+            } else {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub async fn probe_command(argv: &[String], conn_timeout: Duration) -> Result<()> {
+    let (program, args) = argv
+        .split_first()
+        .ok_or_else(|| Error::Config("Empty command".to_string()))?;
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let shown = join_words(argv);
+    let output = tokio::task::spawn_blocking(move || run(command, None, conn_timeout))
+        .await
+        .map_err(|e| Error::Connection(e.to_string()))?
+        .map_err(|e| Error::Config(format!("Cannot run {program}: {e}")))?
+        .ok_or_else(|| {
+            Error::Connection(format!(
+                "Command timeout after {}",
+                human::duration(conn_timeout)
+            ))
+        })?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = stderr
+        .lines()
+        .rfind(|l| !l.trim().is_empty())
+        .unwrap_or_default();
+    let status = output.status.code().map_or_else(
+        || "was killed by a signal".to_string(),
+        |code| format!("exited with code {code}"),
+    );
+    Err(Error::Connection(if detail.is_empty() {
+        format!("{shown} {status}")
+    } else {
+        format!("{shown} {status}: {}", detail.trim())
+    }))
+}
@@ -1,113 +1,1190 @@
+use core::net::SocketAddr;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use std::time::Duration;
 
-use tokio::net::TcpStream;
+use reqwest::{Method, StatusCode};
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+use tokio::net::{TcpSocket, TcpStream};
 use tokio::task::JoinSet;
-use tokio::time::{Instant, sleep, timeout};
+use tokio::time::{Instant, sleep, sleep_until, timeout, timeout_at};
 
-use crate::types::{Error, Header, Result, Target, WaitConfig};
+use crate::command::probe_command;
+use crate::dns::{ServerResolver, resolve};
+use crate::etcd::probe_etcd;
+use crate::human;
+use crate::json::Value;
+use crate::ldap::ldap_bind;
+use crate::nats::nats_handshake;
+use crate::s3::{probe_s3, sha256_hex};
+use crate::ssh::probe_via;
+use crate::tls::{check_expiry, tls_handshake};
+use crate::types::{
+    BearerToken, CancelReason, Error, FileOptions, Hostname, HttpOptions, Pending, ProcessOptions,
+    ProcessSelector, Result, RetryDecision, Target, TargetError, TargetFailure, TargetKind,
+    TargetResult, TargetState, TargetStatus, TcpOptions, TlsOptions, WaitConfig, WaitResult,
+};
+use crate::vault::probe_vault;
+use crate::zookeeper::ruok;
 
-async fn try_tcp_connect(host: &str, port: u16, conn_timeout: Duration) -> Result<()> {
-    timeout(conn_timeout, TcpStream::connect((host, port)))
-        .await
-        .map_err(|_| {
-            Error::Connection(format!(
-                "Connection timeout after {}ms",
-                conn_timeout.as_millis()
-            ))
-        })?
-        .map_err(|e| Error::Connection(e.to_string()))?;
+async fn within<F>(conn_timeout: Duration, what: &str, attempt: F) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    timeout(conn_timeout, attempt).await.map_err(|_| {
+        Error::Connection(format!(
+            "{what} timeout after {}",
+            human::duration(conn_timeout)
+        ))
+    })?
+}
+
+pub fn connect_error(e: &io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => Error::Refused(e.to_string()),
+        _ => Error::Connection(e.to_string()),
+    }
+}
+
+fn is_refused(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<io::Error>() {
+            return io.kind() == io::ErrorKind::ConnectionRefused;
+        }
+        source = err.source();
+    }
+    false
+}
+
+fn probe_socket(
+    addr: SocketAddr,
+    options: TcpOptions,
+    config: &WaitConfig,
+) -> io::Result<TcpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if let Some(ip) = config.bind_address {
+        socket.bind(&SocketAddr::new(ip, 0).into())?;
+    }
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    if let Some(interface) = &config.interface {
+        socket.bind_device(Some(interface.as_bytes()))?;
+    }
+    socket.set_tcp_nodelay(options.nodelay)?;
+    if let Some(time) = options.keepalive {
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?;
+    }
+    if options.linger.is_some() {
+        socket.set_linger(options.linger)?;
+    }
+    socket.set_nonblocking(true)?;
+    Ok(TcpSocket::from_std_stream(socket.into()))
+}
+
+fn settle(
+    addr: SocketAddr,
+    connected: io::Result<TcpStream>,
+    failures: &mut Vec<(SocketAddr, io::Error)>,
+) -> Option<TcpStream> {
+    match connected {
+        Ok(stream) => {
+            tracing::debug!(%addr, "connected");
+            Some(stream)
+        }
+        Err(e) => {
+            tracing::debug!(%addr, errno = e.raw_os_error(), error = %e, "connect failed");
+            failures.push((addr, e));
+            None
+        }
+    }
+}
+
+pub async fn tcp_connect(
+    addrs: &[SocketAddr],
+    options: TcpOptions,
+    config: &WaitConfig,
+) -> Result<TcpStream> {
+    let mut failures = Vec::with_capacity(addrs.len());
+    if config.race_addresses && addrs.len() > 1 {
+        let mut set = JoinSet::new();
+        for &addr in addrs {
+            match probe_socket(addr, options, config) {
+                Ok(socket) => {
+                    set.spawn(async move { (addr, socket.connect(addr).await) });
+                }
+                Err(e) => failures.push((addr, e)),
+            }
+        }
+        // Dropping the set aborts the connects that are still pending.
+        while let Some(joined) = set.join_next().await {
+            let (addr, connected) = joined.map_err(|e| Error::Connection(e.to_string()))?;
+            if let Some(stream) = settle(addr, connected, &mut failures) {
+                return Ok(stream);
+            }
+        }
+    } else {
+        for &addr in addrs {
+            let connected = match probe_socket(addr, options, config) {
+                Ok(socket) => socket.connect(addr).await,
+                Err(e) => Err(e),
+            };
+            if let Some(stream) = settle(addr, connected, &mut failures) {
+                return Ok(stream);
+            }
+        }
+    }
+
+    let refused = failures
+        .iter()
+        .all(|(_, e)| e.kind() == io::ErrorKind::ConnectionRefused);
+    let message = match failures.as_slice() {
+        [(_, e)] => return Err(connect_error(e)),
+        failures => {
+            let failures: Vec<String> = failures
+                .iter()
+                .map(|(addr, e)| format!("{addr}: {e}"))
+                .collect();
+            format!(
+                "all {} addresses failed ({})",
+                failures.len(),
+                failures.join("; ")
+            )
+        }
+    };
+    Err(if refused {
+        Error::Refused(message)
+    } else {
+        Error::Connection(message)
+    })
+}
+
+fn client_builder(config: &WaitConfig, tls_info: bool) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder()
+        .user_agent(&config.user_agent)
+        .tls_info(tls_info)
+        .pool_max_idle_per_host(0)
+        .local_address(config.bind_address);
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    let builder = match &config.interface {
+        Some(interface) => builder.interface(interface),
+        None => builder,
+    };
+    if config.dns_servers.is_empty() {
+        builder
+    } else {
+        builder.dns_resolver(ServerResolver(config.dns_servers.clone()))
+    }
+}
+
+pub fn http_client(config: &WaitConfig, tls_info: bool) -> Result<reqwest::Client> {
+    client_builder(config, tls_info)
+        .build()
+        .map_err(|e| Error::Config(format!("HTTP client error: {e}")))
+}
+
+// The socket is a client setting, so these targets get a client of their own.
+#[cfg(unix)]
+fn unix_socket_client(config: &WaitConfig, path: &Path, tls_info: bool) -> Result<reqwest::Client> {
+    client_builder(config, tls_info)
+        .unix_socket(path.to_path_buf())
+        .build()
+        .map_err(|e| Error::Config(format!("HTTP client error: {e}")))
+}
+
+#[cfg(not(unix))]
+fn unix_socket_client(
+    _config: &WaitConfig,
+    _path: &Path,
+    _tls_info: bool,
+) -> Result<reqwest::Client> {
+    Err(Error::Config(
+        "--unix-socket is only supported on Unix".to_string(),
+    ))
+}
+
+fn check_file(path: &Path, options: &FileOptions) -> Result<()> {
+    let shown = path.display();
+    let metadata = std::fs::metadata(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::Connection(format!("{shown} does not exist")),
+        _ => Error::Connection(format!("Cannot access {shown}: {e}")),
+    })?;
+
+    if options.non_empty && metadata.len() == 0 {
+        return Err(Error::Response(format!("{shown} is empty")));
+    }
+    if let Some(text) = &options.contains {
+        let content = std::fs::read(path)
+            .map_err(|e| Error::Connection(format!("Failed to read {shown}: {e}")))?;
+        if !content
+            .windows(text.len())
+            .any(|window| window == text.as_bytes())
+        {
+            return Err(Error::Response(format!(
+                "{shown} does not contain '{text}'"
+            )));
+        }
+    }
     Ok(())
 }
 
+#[cfg(target_os = "linux")]
+fn check_process(selector: &ProcessSelector, options: &ProcessOptions) -> Result<()> {
+    crate::process::check_process(selector, options)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_process(selector: &ProcessSelector, _options: &ProcessOptions) -> Result<()> {
+    Err(Error::Config(format!(
+        "Process targets are only supported on Linux: '{selector}'"
+    )))
+}
+
+fn check_port_free(addr: SocketAddr) -> Result<()> {
+    match std::net::TcpListener::bind(addr) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            Err(Error::Connection(format!("{addr} is still in use")))
+        }
+        Err(e) => Err(Error::Config(format!("Cannot bind {addr}: {e}"))),
+    }
+}
+
+async fn within_blocking<F>(conn_timeout: Duration, what: &str, check: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    within(conn_timeout, what, async {
+        tokio::task::spawn_blocking(check)
+            .await
+            .map_err(|e| Error::Connection(e.to_string()))?
+    })
+    .await
+}
+
+pub fn request_error(url: &reqwest::Url, e: &reqwest::Error) -> Error {
+    if e.is_builder() {
+        Error::Config(format!("Invalid HTTP request for {url}: {e}"))
+    } else {
+        let message = format!("HTTP request failed for {url}: {e}");
+        if is_refused(e) {
+            Error::Refused(message)
+        } else {
+            Error::Connection(message)
+        }
+    }
+}
+
+pub async fn fetch_json(
+    url: &reqwest::Url,
+    body: Option<&Value>,
+    headers: &[(&str, &str)],
+    config: &WaitConfig,
+    conn_timeout: Duration,
+) -> Result<Value> {
+    let client = match &config.http_client {
+        Some(client) => client.clone(),
+        None => http_client(config, false)?,
+    };
+    let mut request = match body {
+        Some(body) => client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string()),
+        None => client.get(url.clone()),
+    };
+    request = request.timeout(conn_timeout);
+    for (key, value) in headers {
+        request = request.header(*key, *value);
+    }
+    let response = request.send().await.map_err(|e| request_error(url, &e))?;
+    let status = response.status();
+    tracing::debug!(%url, status = status.as_u16(), "response");
+    if !status.is_success() {
+        return Err(Error::Status {
+            expected: "2xx".to_string(),
+            actual: status,
+        });
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|e| Error::Connection(format!("Failed to read body from {url}: {e}")))?;
+    Value::parse(&text).ok_or_else(|| Error::Response(format!("{url} did not return JSON")))
+}
+
 async fn try_http_connect(
     url: &reqwest::Url,
-    headers: &[Header],
+    options: &HttpOptions,
+    config: &WaitConfig,
     conn_timeout: Duration,
 ) -> Result<()> {
-    let client = reqwest::Client::builder()
-        .timeout(conn_timeout)
-        .build()
-        .map_err(|e| Error::Connection(format!("HTTP client error for {url}: {e}")))?;
+    let tls_info = options.cert_min_validity.is_some();
+    let client = match (&options.unix_socket, &config.http_client) {
+        (Some(path), _) => unix_socket_client(config, path, tls_info)?,
+        (None, Some(client)) => client.clone(),
+        (None, None) => http_client(config, tls_info)?,
+    };
 
-    let mut request = client.get(url.clone());
-    for (key, value) in headers {
-        request = request.header(key, value);
-    }
+    let needs_body = options.min_content_length.is_some()
+        || options.graphql_query.is_some()
+        || options.expect_json.is_some()
+        || options.expect_sha256.is_some();
+    let bearer = options
+        .bearer_token
+        .as_ref()
+        .map(BearerToken::read)
+        .transpose()?;
+    let request = |method: Method, limit: Duration| {
+        let mut request = match &options.graphql_query {
+            Some(query) => client
+                .post(url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(
+                    Value::Object(vec![("query".into(), Value::String(query.clone()))]).to_string(),
+                ),
+            None => client.request(method, url.clone()),
+        };
+        request = request.timeout(limit);
+        for (key, value) in &options.headers {
+            request = request.header(key, value);
+        }
+        if let Some((user, password)) = &options.basic_auth {
+            request = request.basic_auth(user, password.as_ref());
+        }
+        if let Some(token) = &bearer {
+            request = request.bearer_auth(token);
+        }
+        request
+    };
 
-    let response = request
+    let started = Instant::now();
+    let head = options.head_first && !needs_body;
+    let method = if head { Method::HEAD } else { Method::GET };
+    let mut response = request(method, conn_timeout)
         .send()
         .await
-        .map_err(|e| Error::Connection(format!("HTTP request failed for {url}: {e}")))?;
+        .map_err(|e| request_error(url, &e))?;
+    if head
+        && matches!(
+            response.status(),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        )
+    {
+        tracing::debug!(%url, "HEAD rejected, retrying with GET");
+        response = request(Method::GET, conn_timeout.saturating_sub(started.elapsed()))
+            .send()
+            .await
+            .map_err(|e| request_error(url, &e))?;
+    }
+    let elapsed = started.elapsed();
+    tracing::debug!(
+        %url,
+        status = response.status().as_u16(),
+        elapsed = %human::duration(elapsed),
+        "response"
+    );
+
+    if let Some(min_validity) = options.cert_min_validity
+        && url.scheme() == "https"
+    {
+        check_certificate_validity(&response, min_validity)?;
+    }
 
     let status = response.status();
-    if status.is_success() {
-        Ok(())
-    } else {
-        Err(Error::Connection(format!(
-            "Expected 2xx status, got {status}"
-        )))
+    if !status.is_success() {
+        return Err(Error::Status {
+            expected: "2xx".to_string(),
+            actual: status,
+        });
+    }
+
+    if let Some(max) = options.max_response_time
+        && elapsed > max
+    {
+        return Err(Error::Response(format!(
+            "Response took {}, limit is {}",
+            human::duration(elapsed),
+            human::duration(max)
+        )));
+    }
+
+    for (name, expected) in &options.expect_headers {
+        let actual = response
+            .headers()
+            .get(name)
+            .ok_or_else(|| Error::Response(format!("Missing expected header '{name}'")))?;
+        if let Some(expected) = expected
+            && actual.to_str().map(str::trim).ok() != Some(expected.as_str())
+        {
+            return Err(Error::Response(format!(
+                "Expected header '{name}: {expected}', got {actual:?}"
+            )));
+        }
+    }
+
+    if !needs_body {
+        return Ok(());
+    }
+
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| Error::Connection(format!("Failed to read body from {url}: {e}")))?;
+
+    if let Some(min) = options.min_content_length
+        && body.len() < min
+    {
+        return Err(Error::Response(format!(
+            "Expected at least {min} body bytes, got {}",
+            body.len()
+        )));
+    }
+
+    if options.graphql_query.is_some() {
+        check_graphql_response(&body)?;
+    }
+
+    if let Some(expected) = &options.expect_json {
+        let actual = core::str::from_utf8(&body)
+            .ok()
+            .and_then(Value::parse)
+            .ok_or_else(|| Error::Response(format!("{url} did not return JSON")))?
+            .canonical()
+            .to_string();
+        if &actual != expected {
+            return Err(Error::Response(
+                "Response JSON does not match the expected body".to_string(),
+            ));
+        }
+    }
+
+    if let Some(expected) = &options.expect_sha256 {
+        let actual = sha256_hex(&body);
+        if &actual != expected {
+            return Err(Error::Response(format!(
+                "Expected body SHA-256 {expected}, got {actual}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_certificate_validity(response: &reqwest::Response, min_validity: Duration) -> Result<()> {
+    let cert = response
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .and_then(reqwest::tls::TlsInfo::peer_certificate);
+    check_expiry(cert, min_validity)
+}
+
+async fn try_tls_connect(
+    host: &Hostname,
+    port: u16,
+    options: &TlsOptions,
+    config: &WaitConfig,
+    conn_timeout: Duration,
+) -> Result<()> {
+    within(
+        conn_timeout,
+        "TLS handshake",
+        tls_handshake(host, port, options, config),
+    )
+    .await
+}
+
+fn check_graphql_response(body: &[u8]) -> Result<()> {
+    let response = core::str::from_utf8(body)
+        .ok()
+        .and_then(Value::parse)
+        .ok_or_else(|| Error::Response("GraphQL response is not valid JSON".to_string()))?;
+
+    match response.get("errors") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(errors)) if errors.is_empty() => {}
+        Some(errors) => {
+            return Err(Error::Response(format!("GraphQL errors: {errors}")));
+        }
+    }
+
+    match response.get("data") {
+        None | Some(Value::Null) => {
+            Err(Error::Response("GraphQL response has no data".to_string()))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn pipe_connect(path: &str) -> Result<()> {
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    match tokio::net::windows::named_pipe::ClientOptions::new().open(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => Ok(()),
+        Err(e) => Err(connect_error(&e)),
+    }
+}
+
+#[cfg(not(windows))]
+fn pipe_connect(path: &str) -> Result<()> {
+    Err(Error::Config(format!(
+        "Named pipe targets are only supported on Windows: '{path}'"
+    )))
+}
+
+async fn try_connect(target: &Target, config: &WaitConfig, conn_timeout: Duration) -> Result<()> {
+    if let Some(jump) = &config.via {
+        return probe_via(jump, &target.kind, config, conn_timeout).await;
+    }
+    match &target.kind {
+        TargetKind::Tcp {
+            host,
+            port,
+            options,
+        } => {
+            within(conn_timeout, "Connection", async {
+                let addrs = resolve(host, port.get(), &config.dns_servers).await?;
+                tcp_connect(&addrs, *options, config).await.map(drop)
+            })
+            .await
+        }
+        TargetKind::Addr { addr, options } => {
+            within(conn_timeout, "Connection", async {
+                tcp_connect(&[*addr], *options, config).await.map(drop)
+            })
+            .await
+        }
+        TargetKind::Tls {
+            host,
+            port,
+            options,
+        } => try_tls_connect(host, port.get(), options, config, conn_timeout).await,
+        TargetKind::Http { url, options } => {
+            try_http_connect(url, options, config, conn_timeout).await
+        }
+        TargetKind::Pipe { path } => pipe_connect(path),
+        TargetKind::File { path, options } => {
+            let (path, options) = (path.clone(), options.clone());
+            within_blocking(conn_timeout, "File check", move || {
+                check_file(&path, &options)
+            })
+            .await
+        }
+        TargetKind::Process { selector, options } => {
+            let (selector, options) = (selector.clone(), *options);
+            within_blocking(conn_timeout, "Process check", move || {
+                check_process(&selector, &options)
+            })
+            .await
+        }
+        TargetKind::PortFree { addr } => check_port_free(*addr),
+        TargetKind::Command { argv } => probe_command(argv, conn_timeout).await,
+        TargetKind::ZooKeeper {
+            host,
+            port,
+            options,
+        } => {
+            within(
+                conn_timeout,
+                "ZooKeeper check",
+                ruok(host, port.get(), *options, config),
+            )
+            .await
+        }
+        TargetKind::Nats {
+            host,
+            port,
+            tcp,
+            options,
+        } => {
+            within(
+                conn_timeout,
+                "NATS handshake",
+                nats_handshake(host, port.get(), *tcp, *options, config),
+            )
+            .await
+        }
+        TargetKind::Ldap {
+            host,
+            port,
+            tls,
+            options,
+        } => {
+            within(
+                conn_timeout,
+                "LDAP bind",
+                ldap_bind(host, port.get(), tls.as_ref(), options, config),
+            )
+            .await
+        }
+        TargetKind::S3 { bucket, options } => {
+            probe_s3(bucket.as_deref(), options, config, conn_timeout).await
+        }
+        TargetKind::Vault { url, options } => {
+            probe_vault(url, *options, config, conn_timeout).await
+        }
+        // The health and key requests share one attempt budget.
+        TargetKind::Etcd { url, key } => {
+            within(
+                conn_timeout,
+                "etcd check",
+                probe_etcd(url, key.as_deref(), config, conn_timeout),
+            )
+            .await
+        }
+        #[cfg(feature = "consul")]
+        TargetKind::Consul { service, options } => {
+            crate::consul::probe_consul(service, options, config, conn_timeout).await
+        }
+        TargetKind::Mount { path, fstype } => {
+            let (path, fstype) = (path.clone(), fstype.clone());
+            within_blocking(conn_timeout, "Mount check", move || {
+                crate::mount::check_mount(&path, fstype.as_deref())
+            })
+            .await
+        }
+        TargetKind::Custom { spec, checker, .. } => {
+            within(conn_timeout, "Check", checker.check(spec, conn_timeout)).await
+        }
     }
 }
 
-async fn try_connect(target: &Target, conn_timeout: Duration) -> Result<()> {
-    match target {
-        Target::Tcp { host, port } => try_tcp_connect(host, *port, conn_timeout).await,
-        Target::Http { url, headers } => try_http_connect(url, headers, conn_timeout).await,
+const ADAPTIVE_SAMPLES: usize = 20;
+const ADAPTIVE_MARGIN: Duration = Duration::from_millis(250);
+
+struct AdaptiveTimeout {
+    current: Duration,
+    samples: Vec<Duration>,
+}
+
+impl AdaptiveTimeout {
+    const fn new(initial: Duration) -> Self {
+        Self {
+            current: initial,
+            samples: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, latency: Duration, timed_out: bool) {
+        if timed_out {
+            self.current = self.current.saturating_mul(2);
+            return;
+        }
+        if self.samples.len() == ADAPTIVE_SAMPLES {
+            self.samples.remove(0);
+        }
+        self.samples.push(latency);
+
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let p95 = sorted[(sorted.len() * 95).div_ceil(100) - 1];
+        self.current = p95.saturating_mul(2) + ADAPTIVE_MARGIN;
     }
 }
 
-async fn wait_for_single_target(target: &Target, config: &WaitConfig) -> Result<()> {
-    let deadline = Instant::now() + config.timeout;
+// Retries `check` the way a target is probed, for readiness that isn't a
+// target: the timeouts, retry policy, backoff and pause all come from
+// `config`, and each attempt is cut off after `connection_timeout`.
+pub async fn wait_until<F, Fut>(mut check: F, config: &WaitConfig) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let start = Instant::now();
+    let deadline = config.deadline_from(start);
+    if let Some(delay) = config.initial_delay {
+        sleep_until(deadline.min(start + delay)).await;
+    }
+    probe_until(
+        "condition",
+        |_, conn_timeout| within(conn_timeout, "Check", check()),
+        config,
+        config.target_deadline(deadline),
+        &Progress::default(),
+    )
+    .await
+}
 
+// The attempt and success counters live in `progress`, so they survive the
+// probe task being aborted.
+async fn probe_until<F, Fut>(
+    name: &str,
+    mut check: F,
+    config: &WaitConfig,
+    deadline: Instant,
+    progress: &Progress,
+) -> Result<()>
+where
+    F: FnMut(u32, Duration) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let start = *progress.started.get_or_init(Instant::now);
+    let attempts = || progress.attempts.load(Ordering::Relaxed);
+    let mut last_error: Option<Error> = None;
+    let mut pending: Option<Pending> = None;
+    let mut streak = 0;
+    let mut adaptive = AdaptiveTimeout::new(config.connection_timeout);
+    let mut up_since: Option<Instant> = None;
     loop {
         let now = Instant::now();
         if now >= deadline {
-            return Err(Error::Timeout(target.to_string()));
+            return Err(Error::Timeout {
+                targets: vec![TargetFailure {
+                    target: name.to_string(),
+                    attempts: attempts(),
+                    elapsed: now.duration_since(start),
+                    last_error: last_error.map(Box::new),
+                    pending,
+                }],
+            });
         }
 
-        let remaining = deadline.duration_since(now);
-        let conn_timeout = config.connection_timeout.min(remaining);
+        if let Some(pause) = &config.pause
+            && *pause.borrow()
+        {
+            tracing::info!(probe = name, "paused");
+            let mut pause = pause.clone();
+            if timeout_at(deadline, pause.wait_for(|paused| !paused))
+                .await
+                .is_err()
+            {
+                continue;
+            }
+        }
+
+        // Waiting for a free slot counts against the deadline, not the attempt.
+        let permit = match &config.probe_limit {
+            Some(limit) => match timeout_at(deadline, limit.permits.acquire()).await {
+                Ok(permit) => permit.ok(),
+                Err(_) => continue,
+            },
+            None => None,
+        };
+        let now = Instant::now();
+        let remaining = deadline.saturating_duration_since(now);
+        let conn_timeout = if config.adaptive_timeout {
+            adaptive.current.min(remaining)
+        } else {
+            config.connection_timeout.min(remaining)
+        };
 
-        if try_connect(target, conn_timeout).await.is_ok() {
-            return Ok(());
+        let attempt = progress.attempts.fetch_add(1, Ordering::Relaxed) + 1;
+        let attempt_start = Instant::now();
+        let result = check(attempt, conn_timeout).await;
+        drop(permit);
+        if config.adaptive_timeout {
+            let latency = attempt_start.elapsed();
+            adaptive.observe(latency, latency >= conn_timeout);
+        }
+        match result {
+            Ok(()) => {
+                tracing::debug!(
+                    probe = name,
+                    attempt,
+                    elapsed = %human::duration(attempt_start.elapsed()),
+                    "attempt succeeded"
+                );
+                let successes = progress.successes.fetch_add(1, Ordering::Relaxed) + 1;
+                let up = *up_since.get_or_insert(attempt_start);
+                let up_for = up.elapsed();
+                let unstable = config.stable_for.filter(|&need| up_for < need);
+                // Keep probing until it has succeeded often and long enough;
+                // a failure starts over.
+                let (pause, waiting_for) = if successes < config.success_threshold {
+                    (
+                        config.initial_interval,
+                        Pending::Successes {
+                            have: successes,
+                            need: config.success_threshold,
+                        },
+                    )
+                } else if let Some(need) = unstable {
+                    (
+                        config.initial_interval.min(need - up_for),
+                        Pending::Stability { up_for, need },
+                    )
+                } else {
+                    return Ok(());
+                };
+                let pause = pause.min(deadline.saturating_duration_since(Instant::now()));
+                tracing::debug!(
+                    probe = name,
+                    retry_in = %human::duration(pause),
+                    "{waiting_for}"
+                );
+                pending = Some(waiting_for);
+                last_error = None;
+                streak = 0;
+                sleep(pause).await;
+                continue;
+            }
+            Err(e) if config.decide(&e) == RetryDecision::Abort => {
+                tracing::info!(
+                    probe = name,
+                    attempt,
+                    code = e.code(),
+                    error = %e,
+                    "attempt failed, not retrying"
+                );
+                return Err(e);
+            }
+            Err(e) => {
+                progress.successes.store(0, Ordering::Relaxed);
+                pending = None;
+                if let Some(up) = up_since.take() {
+                    tracing::info!(
+                        probe = name,
+                        up_for = %human::duration(up.elapsed()),
+                        "went down"
+                    );
+                }
+                let repeated = last_error
+                    .as_ref()
+                    .is_some_and(|last| last.to_string() == e.to_string());
+                streak = if repeated { streak + 1 } else { 1 };
+                last_error = Some(e);
+            }
+        }
+
+        if config.max_attempts.is_some_and(|max| attempt >= max) {
+            return Err(Error::Exhausted(TargetFailure {
+                target: name.to_string(),
+                attempts: attempt,
+                elapsed: start.elapsed(),
+                last_error: last_error.map(Box::new),
+                pending,
+            }));
         }
 
         let remaining = deadline.saturating_duration_since(Instant::now());
-        sleep(config.initial_interval.min(remaining)).await;
+        let interval = config.retry_interval(last_error.as_ref(), streak);
+        if let Some(breaker) = config.circuit_breaker
+            && streak == breaker.threshold
+        {
+            tracing::warn!(
+                probe = name,
+                streak,
+                interval = %human::duration(interval),
+                "identical failures, backing off"
+            );
+        }
+        if let Some(e) = &last_error {
+            tracing::info!(
+                probe = name,
+                attempt,
+                retry_in = %human::duration(interval.min(remaining)),
+                code = e.code(),
+                error = %e,
+                "attempt failed"
+            );
+        }
+        sleep(interval.min(remaining)).await;
     }
 }
 
-pub async fn wait_for_targets(targets: &[Target], config: &WaitConfig) -> Result<()> {
-    if targets.is_empty() {
-        return Ok(());
+struct Check {
+    elapsed: Duration,
+    attempts: u32,
+    successes: u32,
+    result: Result<()>,
+}
+
+// Updated while a target is probed, so a target whose task is aborted once
+// the wait is decided still reports what it did.
+#[derive(Debug, Default)]
+struct Progress {
+    started: OnceLock<Instant>,
+    attempts: AtomicU32,
+    successes: AtomicU32,
+    state: Mutex<TargetState>,
+}
+
+impl Progress {
+    fn set_state(&self, state: TargetState) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = state;
     }
 
-    let mut set = JoinSet::new();
-    for target in targets {
-        let target = target.clone();
-        let config = config.clone();
-        set.spawn(async move { wait_for_single_target(&target, &config).await });
+    fn status(&self, target: Arc<Target>) -> TargetStatus {
+        TargetStatus {
+            target,
+            state: *self.state.lock().unwrap_or_else(PoisonError::into_inner),
+            elapsed: self.started.get().map_or(Duration::ZERO, Instant::elapsed),
+            attempts: self.attempts.load(Ordering::Relaxed),
+            successes: self.successes.load(Ordering::Relaxed),
+        }
     }
 
-    if config.wait_for_any {
-        while let Some(result) = set.join_next().await {
-            if result.unwrap().is_ok() {
-                return Ok(());
+    fn stopped(&self, target: Arc<Target>, reason: CancelReason) -> TargetResult {
+        self.set_state(TargetState::Stopped);
+        let (elapsed, error) = match self.started.get() {
+            Some(started) => (started.elapsed(), TargetError::Stopped(reason)),
+            None => (Duration::ZERO, TargetError::Cancelled(reason)),
+        };
+        TargetResult {
+            target,
+            elapsed,
+            attempts: self.attempts.load(Ordering::Relaxed),
+            successes: self.successes.load(Ordering::Relaxed),
+            error: Some(error),
+        }
+    }
+}
+
+async fn wait_for_single_target(
+    target: &Target,
+    config: &WaitConfig,
+    deadline: Instant,
+    progress: &Progress,
+) -> Check {
+    progress.set_state(TargetState::Probing);
+    if let Some(reporter) = &config.reporter {
+        reporter.target_started(target);
+    }
+    let deadline = config.target_deadline(deadline);
+    let result = probe_until(
+        &target.to_string(),
+        |attempt, conn_timeout| async move {
+            let result = try_connect(target, config, conn_timeout).await;
+            if let (Err(e), Some(reporter)) = (&result, &config.reporter) {
+                reporter.attempt_failed(target, attempt, e);
             }
+            result
+        },
+        config,
+        deadline,
+        progress,
+    )
+    .await;
+
+    let check = Check {
+        elapsed: progress
+            .started
+            .get()
+            .map_or(Duration::ZERO, Instant::elapsed),
+        attempts: progress.attempts.load(Ordering::Relaxed),
+        successes: progress.successes.load(Ordering::Relaxed),
+        result,
+    };
+    progress.set_state(if check.result.is_ok() {
+        TargetState::Ready
+    } else {
+        TargetState::Failed
+    });
+    if let Some(reporter) = &config.reporter {
+        reporter.target_finished(target, check.elapsed, check.attempts, &check.result);
+    }
+    check
+}
+
+async fn wait_sequentially(
+    targets: &[Arc<Target>],
+    config: &WaitConfig,
+    deadline: Instant,
+    progress: &[Arc<Progress>],
+) -> Vec<Option<Check>> {
+    let mut checks: Vec<Option<Check>> = targets.iter().map(|_| None).collect();
+    let needed = config.quorum.needed(targets.len());
+    let (mut ready, mut failed) = (0, 0);
+    for (slot, target) in targets.iter().enumerate() {
+        let check = wait_for_single_target(target, config, deadline, &progress[slot]).await;
+        if check.result.is_ok() {
+            ready += 1;
+        } else {
+            failed += 1;
+        }
+        checks[slot] = Some(check);
+        if ready >= needed || failed > targets.len() - needed {
+            break;
         }
-        return Err(Error::Timeout("all targets timed out".into()));
     }
+    checks
+}
 
-    let mut failed = Vec::new();
-    while let Some(result) = set.join_next().await {
-        if let Err(e) = result.unwrap() {
-            failed.push(e.to_string());
+async fn wait_concurrently(
+    targets: &[Arc<Target>],
+    config: &Arc<WaitConfig>,
+    deadline: Instant,
+    progress: &[Arc<Progress>],
+) -> Vec<Option<Check>> {
+    let mut checks: Vec<Option<Check>> = targets.iter().map(|_| None).collect();
+    let mut set = JoinSet::new();
+    for (slot, target) in targets.iter().enumerate() {
+        let target = Arc::clone(target);
+        let config = Arc::clone(config);
+        let progress = Arc::clone(&progress[slot]);
+        set.spawn(async move {
+            (
+                slot,
+                wait_for_single_target(&target, &config, deadline, &progress).await,
+            )
+        });
+    }
+
+    let needed = config.quorum.needed(targets.len());
+    let (mut ready, mut failed) = (0, 0);
+    while let Some(joined) = set.join_next().await {
+        let (slot, check) = joined.unwrap();
+        if check.result.is_ok() {
+            ready += 1;
+        } else {
+            failed += 1;
+        }
+        checks[slot] = Some(check);
+        // Stop once the quorum is met or can no longer be.
+        if ready >= needed || failed > targets.len() - needed {
+            set.abort_all();
+            break;
         }
     }
+    checks
+}
 
-    if !failed.is_empty() {
-        return Err(Error::Timeout(failed.join(", ")));
+fn overall_error(checks: Vec<Option<Check>>, config: &WaitConfig) -> Option<Error> {
+    let needed = config.quorum.needed(checks.len());
+    let mut failed: Vec<Error> = Vec::new();
+    let mut ready = 0;
+    for check in checks.into_iter().flatten() {
+        match check.result {
+            Ok(()) => ready += 1,
+            Err(e) => failed.push(e),
+        }
     }
 
-    Ok(())
+    if ready >= needed {
+        return None;
+    }
+
+    let terminal = failed.iter().position(Error::is_fatal).or_else(|| {
+        failed
+            .iter()
+            .position(|e| !matches!(e, Error::Timeout { .. }))
+    });
+    if let Some(pos) = terminal {
+        return Some(failed.swap_remove(pos));
+    }
+
+    if failed.is_empty() {
+        return None;
+    }
+    let targets = failed
+        .into_iter()
+        .flat_map(|e| match e {
+            Error::Timeout { targets } => targets,
+            _ => Vec::new(),
+        })
+        .collect();
+    Some(Error::Timeout { targets })
+}
+
+pub async fn wait_for_targets<I>(targets: I, config: &Arc<WaitConfig>) -> WaitResult
+where
+    I: IntoIterator,
+    I::Item: Into<Arc<Target>>,
+{
+    Watch::new(targets).run(config).await
+}
+
+// The targets of one wait, with duplicates probed once, and how far each got.
+pub struct Watch {
+    targets: Vec<Arc<Target>>,
+    unique: Vec<Arc<Target>>,
+    slots: Vec<usize>,
+    progress: Vec<Arc<Progress>>,
+}
+
+impl Watch {
+    pub fn new<I>(targets: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Arc<Target>>,
+    {
+        // Shared by the probe task and the result; Arc items are not copied.
+        let targets: Vec<Arc<Target>> = targets.into_iter().map(Into::into).collect();
+        let mut unique: Vec<Arc<Target>> = Vec::with_capacity(targets.len());
+        let slots: Vec<usize> = targets
+            .iter()
+            .map(|target| {
+                unique
+                    .iter()
+                    .position(|u| u.kind == target.kind)
+                    .unwrap_or_else(|| {
+                        unique.push(Arc::clone(target));
+                        unique.len() - 1
+                    })
+            })
+            .collect();
+        let progress = unique.iter().map(|_| Arc::default()).collect();
+        Self {
+            targets,
+            unique,
+            slots,
+            progress,
+        }
+    }
+
+    pub fn status(&self) -> Vec<TargetStatus> {
+        self.targets
+            .iter()
+            .zip(&self.slots)
+            .map(|(target, &slot)| self.progress[slot].status(Arc::clone(target)))
+            .collect()
+    }
+
+    pub async fn run(&self, config: &Arc<WaitConfig>) -> WaitResult {
+        let start = Instant::now();
+        let deadline = config.deadline_from(start);
+        if let Some(delay) = config.initial_delay {
+            tracing::info!(delay = %human::duration(delay), "waiting before the first probe");
+            sleep_until(deadline.min(start + delay)).await;
+        }
+        let (unique, progress) = (&self.unique, &self.progress);
+        let checks = if config.sequential {
+            wait_sequentially(unique, config, deadline, progress).await
+        } else {
+            wait_concurrently(unique, config, deadline, progress).await
+        };
+
+        // Targets are only skipped once the wait has been decided.
+        let ready = checks
+            .iter()
+            .flatten()
+            .filter(|check| check.result.is_ok())
+            .count();
+        let reason = if ready >= config.quorum.needed(checks.len()) {
+            CancelReason::AnyReady
+        } else if Instant::now() >= deadline {
+            CancelReason::Deadline
+        } else {
+            CancelReason::DependencyFailed
+        };
+        let results = self
+            .targets
+            .iter()
+            .cloned()
+            .zip(self.slots.iter().copied())
+            .map(|(target, slot)| match &checks[slot] {
+                Some(check) => TargetResult {
+                    target,
+                    elapsed: check.elapsed,
+                    attempts: check.attempts,
+                    successes: check.successes,
+                    error: check.result.as_ref().err().cloned().map(TargetError::from),
+                },
+                None => self.progress[slot].stopped(target, reason),
+            })
+            .collect();
+
+        let result = WaitResult {
+            targets: results,
+            elapsed: start.elapsed(),
+            error: overall_error(checks, config),
+        };
+        if let Some(reporter) = &config.reporter {
+            reporter.all_done(&result);
+        }
+        result
+    }
 }
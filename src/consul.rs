@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use reqwest::Url;
+
+use crate::connection::fetch_json;
+use crate::json::Value;
+use crate::types::{ConsulOptions, Error, Result, WaitConfig};
+
+const DEFAULT_ADDR: &str = "http://127.0.0.1:8500";
+
+pub fn parse_addr(addr: &str) -> Result<Url> {
+    let with_scheme = if addr.contains("://") {
+        addr.to_string()
+    } else {
+        format!("http://{addr}")
+    };
+    Url::parse(&with_scheme)
+        .map_err(|e| Error::Config(format!("Invalid Consul address '{addr}': {e}")))
+}
+
+pub async fn probe_consul(
+    service: &str,
+    options: &ConsulOptions,
+    config: &WaitConfig,
+    conn_timeout: Duration,
+) -> Result<()> {
+    let mut url = match &options.addr {
+        Some(addr) => addr.clone(),
+        None => parse_addr(DEFAULT_ADDR)?,
+    };
+    url.path_segments_mut()
+        .map_err(|()| Error::Config("Consul address must be an http(s) URL".to_string()))?
+        .pop_if_empty()
+        .extend(["v1", "health", "service", service]);
+    url.set_query(Some("passing=true"));
+
+    let headers: Vec<(&str, &str)> = options
+        .token
+        .as_deref()
+        .map(|token| ("X-Consul-Token", token))
+        .into_iter()
+        .collect();
+    let Value::Array(instances) = fetch_json(&url, None, &headers, config, conn_timeout).await?
+    else {
+        return Err(Error::Response(format!(
+            "Unexpected Consul response from {url}"
+        )));
+    };
+
+    let need = options.min_healthy.max(1);
+    if instances.len() < need as usize {
+        return Err(Error::Response(format!(
+            "{} healthy instance(s) of '{service}' registered, need {need}",
+            instances.len()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket, lookup_host};
+use tokio::sync::OnceCell;
+use tokio::time::{Instant, timeout};
+
+use crate::types::{Error, Hostname, Result};
+
+// Targets on the same host poll in step, so a lookup started within this
+// window is shared instead of repeated. Later rounds resolve again.
+const SHARE_WINDOW: Duration = Duration::from_millis(500);
+
+const DNS_PORT: u16 = 53;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u8 = 3;
+const FLAG_TRUNCATED: u8 = 0x02;
+const MAX_MESSAGE: usize = 4096;
+
+type Lookup = Arc<OnceCell<Result<Vec<IpAddr>>>>;
+
+// Every lookup asks for both A and AAAA records, so the host and the servers
+// asked are all that can make two answers differ.
+type LookupKey = (String, Vec<SocketAddr>);
+
+static LOOKUPS: Mutex<BTreeMap<LookupKey, (Instant, Lookup)>> = Mutex::new(BTreeMap::new());
+static QUERY_ID: AtomicU16 = AtomicU16::new(0);
+
+fn shared_lookup(key: &LookupKey) -> Lookup {
+    let mut lookups = LOOKUPS.lock().unwrap_or_else(PoisonError::into_inner);
+    let now = Instant::now();
+    match lookups.get(key) {
+        Some((started, lookup)) if now.duration_since(*started) < SHARE_WINDOW => {
+            Arc::clone(lookup)
+        }
+        _ => {
+            let lookup = Lookup::default();
+            lookups.insert(key.clone(), (now, Arc::clone(&lookup)));
+            lookup
+        }
+    }
+}
+
+fn forget(key: &LookupKey, lookup: &Lookup) {
+    let mut lookups = LOOKUPS.lock().unwrap_or_else(PoisonError::into_inner);
+    if lookups
+        .get(key)
+        .is_some_and(|(_, cached)| Arc::ptr_eq(cached, lookup))
+    {
+        lookups.remove(key);
+    }
+}
+
+fn encode_query(id: u16, host: &str, qtype: u16) -> Result<Vec<u8>> {
+    let mut message = id.to_be_bytes().to_vec();
+    // Recursion desired, one question.
+    message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in host.split('.') {
+        let len = u8::try_from(label.len())
+            .ok()
+            .filter(|&len| (1..64).contains(&len))
+            .ok_or_else(|| Error::Dns(format!("{host}: invalid name")))?;
+        message.push(len);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&qtype.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // Compression pointer: the name ends here.
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + usize::from(len),
+        }
+    }
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(pos)?,
+        *message.get(pos + 1)?,
+    ]))
+}
+
+// Recursive servers put the records a CNAME points to in the answer
+// section, so collecting A/AAAA records is enough.
+fn parse_answers(message: &[u8]) -> Option<Vec<IpAddr>> {
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos)? + 4;
+    }
+    let mut ips = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(message, pos)?;
+        let rtype = read_u16(message, pos)?;
+        let len = usize::from(read_u16(message, pos + 8)?);
+        let data = message.get(pos + 10..pos + 10 + len)?;
+        match (rtype, data.len()) {
+            (TYPE_A, 4) => ips.push(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(data).ok()?))),
+            (TYPE_AAAA, 16) => {
+                ips.push(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?)))
+            }
+            _ => {}
+        }
+        pos += 10 + len;
+    }
+    Some(ips)
+}
+
+fn answers(reply: &[u8], id: u16) -> bool {
+    reply.len() >= 12 && reply[..2] == id.to_be_bytes() && reply[2] & 0x80 != 0
+}
+
+async fn exchange_udp(server: SocketAddr, request: &[u8], id: u16) -> std::io::Result<Vec<u8>> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(request).await?;
+    let mut response = [0u8; MAX_MESSAGE];
+    loop {
+        let len = socket.recv(&mut response).await?;
+        // Ignore stray datagrams that don't answer this query.
+        if answers(&response[..len], id) {
+            return Ok(response[..len].to_vec());
+        }
+    }
+}
+
+// RFC 1035 4.2.2: each message is preceded by its length.
+async fn exchange_tcp(server: SocketAddr, request: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(server).await?;
+    let len = u16::try_from(request.len()).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+    let mut framed = len.to_be_bytes().to_vec();
+    framed.extend_from_slice(request);
+    stream.write_all(&framed).await?;
+    let len = stream.read_u16().await?;
+    let mut response = vec![0; usize::from(len)];
+    stream.read_exact(&mut response).await?;
+    Ok(response)
+}
+
+async fn query(server: SocketAddr, host: &str, qtype: u16) -> Result<Vec<IpAddr>> {
+    let id = QUERY_ID.fetch_add(1, Ordering::Relaxed);
+    let request = encode_query(id, host, qtype)?;
+    let io_error = |e: std::io::Error| Error::Dns(format!("{host}: {server}: {e}"));
+    let no_answer = |_| Error::Dns(format!("{host}: {server} did not answer"));
+
+    let mut reply = timeout(QUERY_TIMEOUT, exchange_udp(server, &request, id))
+        .await
+        .map_err(no_answer)?
+        .map_err(io_error)?;
+    // Answers that don't fit in a datagram are truncated; ask again over TCP.
+    if reply[2] & FLAG_TRUNCATED != 0 {
+        tracing::debug!(host, %server, "truncated reply, retrying over TCP");
+        reply = timeout(QUERY_TIMEOUT, exchange_tcp(server, &request))
+            .await
+            .map_err(no_answer)?
+            .map_err(io_error)?;
+        if !answers(&reply, id) {
+            return Err(Error::Dns(format!("{host}: malformed reply from {server}")));
+        }
+    }
+
+    match reply[3] & 0x0f {
+        0 => parse_answers(&reply)
+            .ok_or_else(|| Error::Dns(format!("{host}: malformed reply from {server}"))),
+        RCODE_NXDOMAIN => Err(Error::Dns(format!("{host}: no such host"))),
+        rcode => Err(Error::Dns(format!(
+            "{host}: {server} answered with rcode {rcode}"
+        ))),
+    }
+}
+
+// Servers are tried in order; the first one returning addresses wins.
+async fn query_servers(host: &str, servers: &[SocketAddr]) -> Result<Vec<IpAddr>> {
+    let mut last_error = None;
+    for &server in servers {
+        let (v4, v6) = tokio::join!(query(server, host, TYPE_A), query(server, host, TYPE_AAAA));
+        let mut ips = Vec::new();
+        for answer in [v4, v6] {
+            match answer {
+                Ok(found) => ips.extend(found),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if !ips.is_empty() {
+            return Ok(ips);
+        }
+    }
+    Err(last_error.unwrap_or_else(|| Error::Dns(format!("{host}: no addresses found"))))
+}
+
+async fn lookup_ips(host: &str, servers: &[SocketAddr]) -> Result<Vec<IpAddr>> {
+    let ips: Vec<IpAddr> = if servers.is_empty() {
+        lookup_host((host, 0))
+            .await
+            .map_err(|e| Error::Dns(format!("{host}: {e}")))?
+            .map(|addr| addr.ip())
+            .collect()
+    } else {
+        query_servers(host, servers).await?
+    };
+    if ips.is_empty() {
+        return Err(Error::Dns(format!("{host}: no addresses found")));
+    }
+    let shown: Vec<String> = ips.iter().map(ToString::to_string).collect();
+    tracing::debug!(host, addresses = %shown.join(", "), "resolved");
+    Ok(ips)
+}
+
+async fn resolve_name(host: &str, servers: &[SocketAddr]) -> Result<Vec<IpAddr>> {
+    let key = (host.to_string(), servers.to_vec());
+    let lookup = shared_lookup(&key);
+    match lookup.get_or_init(|| lookup_ips(host, servers)).await {
+        Ok(ips) => Ok(ips.clone()),
+        Err(e) => {
+            // Failures are only shared with lookups already waiting on them.
+            forget(&key, &lookup);
+            Err(e.clone())
+        }
+    }
+}
+
+pub async fn resolve(
+    host: &Hostname,
+    port: u16,
+    servers: &[SocketAddr],
+) -> Result<Vec<SocketAddr>> {
+    match host {
+        Hostname::Ip(ip) => Ok(vec![SocketAddr::new(*ip, port)]),
+        Hostname::Name(name) => Ok(resolve_name(name, servers)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect()),
+    }
+}
+
+// Lets HTTP-based probes resolve through --dns-server too.
+pub struct ServerResolver(pub Vec<SocketAddr>);
+
+impl Resolve for ServerResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let servers = self.0.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let ips = resolve_name(&host, &servers).await?;
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+pub fn parse_server(s: &str) -> Result<SocketAddr> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, DNS_PORT)))
+        .map_err(|_| Error::Config(format!("Invalid DNS server '{s}': expected IP or IP:PORT")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(host: &str, servers: &[&str]) -> LookupKey {
+        let servers = servers.iter().map(|s| parse_server(s).unwrap()).collect();
+        (host.to_string(), servers)
+    }
+
+    // Answers an A query for `request` with `ip`, or with no records and
+    // the truncated flag set.
+    fn reply(request: &[u8], ip: Option<Ipv4Addr>) -> Vec<u8> {
+        let mut message = request[..2].to_vec();
+        let flags = if ip.is_some() {
+            0x81
+        } else {
+            0x81 | FLAG_TRUNCATED
+        };
+        message.extend_from_slice(&[flags, 0x80, 0, 1, 0, u8::from(ip.is_some()), 0, 0, 0, 0]);
+        message.extend_from_slice(&request[12..]);
+        if let Some(ip) = ip {
+            message.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+            message.extend_from_slice(&ip.octets());
+        }
+        message
+    }
+
+    #[test]
+    fn encodes_queries() {
+        let query = encode_query(0xbeef, "db.test", TYPE_AAAA).unwrap();
+        assert_eq!(
+            query,
+            [
+                &[0xbe, 0xef, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0][..],
+                b"\x02db\x04test\x00",
+                &[0, 28, 0, 1],
+            ]
+            .concat()
+        );
+        for host in ["", "db..test", "db.test.", &"a".repeat(64)] {
+            assert!(matches!(encode_query(1, host, TYPE_A), Err(Error::Dns(_))));
+        }
+    }
+
+    #[test]
+    fn parses_answers_past_cnames() {
+        let mut message = encode_query(7, "www.test", TYPE_A).unwrap();
+        message[2] = 0x81;
+        message[7] = 3;
+        // www.test CNAME db.test, then db.test A and AAAA via pointers.
+        message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 5]);
+        message.extend_from_slice(b"\x02db\xc0\x10");
+        message.extend_from_slice(&[0xc0, 38, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 7]);
+        message.extend_from_slice(&[0xc0, 38, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16]);
+        message.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        assert!(answers(&message, 7));
+        assert!(!answers(&message, 8));
+        assert_eq!(
+            parse_answers(&message),
+            Some(vec![
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7)),
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ])
+        );
+        assert_eq!(parse_answers(&message[..message.len() - 1]), None);
+    }
+
+    #[tokio::test]
+    async fn truncated_reply_is_retried_over_tcp() {
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = udp.local_addr().unwrap();
+        let tcp = tokio::net::TcpListener::bind(server).await.unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, peer) = udp.recv_from(&mut buf).await.unwrap();
+            udp.send_to(&reply(&buf[..len], None), peer).await.unwrap();
+        });
+        tokio::spawn(async move {
+            let (mut stream, _) = tcp.accept().await.unwrap();
+            let len = stream.read_u16().await.unwrap();
+            let mut request = vec![0; usize::from(len)];
+            stream.read_exact(&mut request).await.unwrap();
+            let answer = reply(&request, Some(Ipv4Addr::new(10, 1, 2, 3)));
+            let len = u16::try_from(answer.len()).unwrap();
+            stream.write_all(&len.to_be_bytes()).await.unwrap();
+            stream.write_all(&answer).await.unwrap();
+        });
+
+        let ips = query(server, "big.test", TYPE_A).await.unwrap();
+        assert_eq!(ips, [IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3))]);
+    }
+
+    #[test]
+    fn lookups_are_shared_per_host_and_servers() {
+        let system = shared_lookup(&key("shared.test", &[]));
+        let first = shared_lookup(&key("shared.test", &["10.0.0.1"]));
+        let second = shared_lookup(&key("shared.test", &["10.0.0.2"]));
+        assert!(!Arc::ptr_eq(&system, &first));
+        assert!(!Arc::ptr_eq(&first, &second));
+        let again = shared_lookup(&key("shared.test", &["10.0.0.1:53"]));
+        assert!(Arc::ptr_eq(&first, &again));
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::Url;
+
+use crate::connection::fetch_json;
+use crate::json::Value;
+use crate::types::{Error, Result, WaitConfig};
+
+fn endpoint(base: &Url, path: &str) -> Result<Url> {
+    base.join(path)
+        .map_err(|e| Error::Config(format!("Invalid etcd endpoint {base}: {e}")))
+}
+
+async fn check_health(base: &Url, config: &WaitConfig, conn_timeout: Duration) -> Result<()> {
+    let url = endpoint(base, "health")?;
+    let health = fetch_json(&url, None, &[], config, conn_timeout).await?;
+    match health.get("health") {
+        Some(Value::String(healthy)) if healthy == "true" => Ok(()),
+        Some(_) => {
+            let reason = match health.get("reason") {
+                Some(Value::String(reason)) if !reason.is_empty() => reason.as_str(),
+                _ => "no leader or quorum",
+            };
+            Err(Error::Response(format!(
+                "etcd at {base} is unhealthy: {reason}"
+            )))
+        }
+        None => Err(Error::Response(format!(
+            "Unexpected etcd health response from {url}"
+        ))),
+    }
+}
+
+async fn check_key(
+    base: &Url,
+    key: &str,
+    config: &WaitConfig,
+    conn_timeout: Duration,
+) -> Result<()> {
+    let url = endpoint(base, "v3/kv/range")?;
+    let body = Value::Object(vec![
+        ("key".into(), Value::String(STANDARD.encode(key))),
+        ("count_only".into(), Value::Bool(true)),
+    ]);
+    let range = fetch_json(&url, Some(&body), &[], config, conn_timeout).await?;
+    // int64 fields are encoded as strings by the gRPC gateway and omitted when zero.
+    let count = match range.get("count") {
+        Some(Value::String(count)) => count.parse().unwrap_or(0),
+        Some(Value::Number(count)) => count.as_u64().unwrap_or(0),
+        _ => 0,
+    };
+    if count == 0 {
+        return Err(Error::Response(format!("etcd key '{key}' does not exist")));
+    }
+    Ok(())
+}
+
+pub async fn probe_etcd(
+    base: &Url,
+    key: Option<&str>,
+    config: &WaitConfig,
+    conn_timeout: Duration,
+) -> Result<()> {
+    check_health(base, config, conn_timeout).await?;
+    if let Some(key) = key {
+        check_key(base, key, config, conn_timeout).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::connection::Watch;
+use crate::types::{Target, TargetStatus, WaitConfig, WaitResult};
+
+/// A wait running in the background, started by [`spawn_wait`].
+///
+/// Dropping the handle stops the wait.
+pub struct WaitHandle {
+    watch: Arc<Watch>,
+    paused: watch::Sender<bool>,
+    task: JoinHandle<WaitResult>,
+}
+
+/// Starts waiting for `targets` on the current Tokio runtime.
+pub fn spawn_wait<I>(targets: I, mut config: WaitConfig) -> WaitHandle
+where
+    I: IntoIterator,
+    I::Item: Into<Arc<Target>>,
+{
+    let (paused, pause) = watch::channel(false);
+    config.pause = Some(pause);
+    let config = Arc::new(config);
+    let watch = Arc::new(Watch::new(targets));
+    let task = tokio::spawn({
+        let watch = Arc::clone(&watch);
+        async move { watch.run(&config).await }
+    });
+    WaitHandle {
+        watch,
+        paused,
+        task,
+    }
+}
+
+impl WaitHandle {
+    /// Holds back new attempts until [`resume`](Self::resume). Attempts
+    /// already running finish, and the timeout keeps counting.
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Where each target stands, in the order they were given.
+    pub fn status(&self) -> Vec<TargetStatus> {
+        self.watch.status()
+    }
+
+    pub async fn join(mut self) -> WaitResult {
+        match (&mut self.task).await {
+            Ok(result) => result,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+impl Drop for WaitHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::types::{TargetOptions, TargetState};
+
+    #[tokio::test]
+    async fn paused_wait_makes_no_attempts_until_resumed() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let target = Target::parse(&addr, &TargetOptions::default()).unwrap();
+
+        let handle = spawn_wait([target], WaitConfig::default());
+        handle.pause();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = handle.status();
+        assert!(handle.is_paused() && !handle.is_finished());
+        assert_eq!(status[0].state, TargetState::Probing);
+        assert_eq!(status[0].attempts, 0);
+
+        handle.resume();
+        let result = handle.join().await;
+        assert!(result.is_success());
+        assert_eq!(result.targets[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn status_reports_each_target() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let options = TargetOptions::default();
+        let targets = [
+            Target::parse(&addr, &options).unwrap(),
+            Target::parse("exec:false", &options).unwrap(),
+        ];
+        let config = WaitConfig {
+            sequential: true,
+            max_attempts: Some(1),
+            ..WaitConfig::default()
+        };
+
+        let handle = spawn_wait(targets, config);
+        assert_eq!(handle.status()[1].state, TargetState::Pending);
+        while !handle.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let states: Vec<_> = handle.status().iter().map(|s| s.state).collect();
+        assert_eq!(states, [TargetState::Ready, TargetState::Failed]);
+    }
+}
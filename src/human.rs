@@ -0,0 +1,16 @@
+use std::time::Duration;
+
+use humantime::FormattedDuration;
+
+// Drops precision nobody reads: whole seconds past a minute, milliseconds
+// past a millisecond, so 83.2145s prints as "1m 23s".
+pub fn duration(d: Duration) -> FormattedDuration {
+    let d = if d >= Duration::from_secs(60) {
+        Duration::from_secs(d.as_secs())
+    } else if d >= Duration::from_millis(1) {
+        Duration::from_millis(d.as_millis() as u64)
+    } else {
+        Duration::from_micros(d.as_micros() as u64)
+    };
+    humantime::format_duration(d)
+}
@@ -0,0 +1,493 @@
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+// Kept as written, so integers past 2^53 and long decimals lose no digits.
+// Equality is exact decimal equality: 1, 1.0 and 10e-1 are equal.
+#[derive(Debug, Clone)]
+pub struct Number(String);
+
+impl Number {
+    pub fn as_u64(&self) -> Option<u64> {
+        self.0.parse().ok()
+    }
+
+    // (negative, significant digits, exponent) with no leading or trailing
+    // zeros in the digits; zero is (false, "", 0).
+    fn decimal(&self) -> (bool, String, i64) {
+        let (negative, text) = match self.0.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, self.0.as_str()),
+        };
+        let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+        let exponent = exponent
+            .strip_prefix('+')
+            .unwrap_or(exponent)
+            .parse::<i64>()
+            .unwrap_or(if exponent.starts_with('-') {
+                i64::MIN / 2
+            } else {
+                i64::MAX / 2
+            });
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{int}{frac}");
+        let significant = digits.trim_end_matches('0');
+        let len = |s: &str| i64::try_from(s.len()).unwrap_or(i64::MAX);
+        let exponent = exponent
+            .saturating_sub(len(frac))
+            .saturating_add(len(&digits) - len(significant));
+        let significant = significant.trim_start_matches('0');
+        if significant.is_empty() {
+            return (false, String::new(), 0);
+        }
+        (negative, significant.to_string(), exponent)
+    }
+
+    fn canonical(&self) -> Self {
+        let (negative, digits, exponent) = self.decimal();
+        let sign = if negative { "-" } else { "" };
+        Self(match (digits.is_empty(), exponent) {
+            (true, _) => "0".to_string(),
+            (false, 0) => format!("{sign}{digits}"),
+            (false, _) => format!("{sign}{digits}e{exponent}"),
+        })
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.decimal() == other.decimal()
+    }
+}
+
+impl From<u64> for Number {
+    fn from(n: u64) -> Self {
+        Self(n.to_string())
+    }
+}
+
+impl From<u32> for Number {
+    fn from(n: u32) -> Self {
+        Self(n.to_string())
+    }
+}
+
+impl From<usize> for Number {
+    fn from(n: usize) -> Self {
+        Self(n.to_string())
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Deeper documents are rejected instead of risking the stack.
+const MAX_DEPTH: usize = 128;
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut parser = Parser {
+            bytes: input.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_ws();
+        (parser.pos == parser.bytes.len()).then_some(value)
+    }
+
+    // Sorts object keys recursively and spells numbers one way, so equal
+    // documents print identically.
+    pub fn canonical(self) -> Self {
+        match self {
+            Self::Number(n) => Self::Number(n.canonical()),
+            Self::Array(items) => Self::Array(items.into_iter().map(Self::canonical).collect()),
+            Self::Object(mut fields) => {
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Self::Object(
+                    fields
+                        .into_iter()
+                        .map(|(key, value)| (key, value.canonical()))
+                        .collect(),
+                )
+            }
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::String(s) => write_escaped(f, s),
+            Self::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if u32::from(c) < 0x20 => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> Option<()> {
+        self.skip_ws();
+        (self.peek() == Some(byte)).then(|| self.pos += 1)
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Option<Value> {
+        let end = self.pos + word.len();
+        (self.bytes.get(self.pos..end) == Some(word.as_bytes())).then(|| {
+            self.pos = end;
+            value
+        })
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_ws();
+        match self.peek()? {
+            b'n' => self.literal("null", Value::Null),
+            b't' => self.literal("true", Value::Bool(true)),
+            b'f' => self.literal("false", Value::Bool(false)),
+            b'"' => self.string().map(Value::String),
+            b'[' | b'{' => {
+                self.depth += 1;
+                if self.depth > MAX_DEPTH {
+                    return None;
+                }
+                let value = if self.peek() == Some(b'[') {
+                    self.array()
+                } else {
+                    self.object()
+                };
+                self.depth -= 1;
+                value
+            }
+            _ => self.number(),
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    // -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Option<Value> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek()? {
+            b'0' => self.pos += 1,
+            b'1'..=b'9' => {
+                self.digits();
+            }
+            _ => return None,
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        let text = core::str::from_utf8(&self.bytes[start..self.pos]).ok()?;
+        Some(Value::Number(Number(text.to_string())))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.eat(b'"')?;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while self
+                .peek()
+                .is_some_and(|b| b >= 0x20 && b != b'"' && b != b'\\')
+            {
+                self.pos += 1;
+            }
+            out.push_str(core::str::from_utf8(&self.bytes[start..self.pos]).ok()?);
+            match self.peek()? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(out);
+                }
+                b'\\' => {
+                    let escape = *self.bytes.get(self.pos + 1)?;
+                    self.pos += 2;
+                    out.push(match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => self.unicode_escape()?,
+                        _ => return None,
+                    });
+                }
+                // Unescaped control character.
+                _ => return None,
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.bytes.get(self.pos..self.pos + 4)?;
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        self.pos += 4;
+        u32::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()
+    }
+
+    // Lone surrogates have no char to decode to and are rejected.
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if self.bytes.get(self.pos..self.pos + 2) != Some(b"\\u") {
+            return None;
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+    }
+
+    fn array(&mut self) -> Option<Value> {
+        self.eat(b'[')?;
+        let mut items = Vec::new();
+        if self.eat(b']').is_some() {
+            return Some(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat(b']').is_some() {
+                return Some(Value::Array(items));
+            }
+            self.eat(b',')?;
+        }
+    }
+
+    fn object(&mut self) -> Option<Value> {
+        self.eat(b'{')?;
+        let mut fields = Vec::new();
+        if self.eat(b'}').is_some() {
+            return Some(Value::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            let key = self.string()?;
+            self.eat(b':')?;
+            fields.push((key, self.value()?));
+            if self.eat(b'}').is_some() {
+                return Some(Value::Object(fields));
+            }
+            self.eat(b',')?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(input: &str) -> String {
+        Value::parse(input).unwrap().canonical().to_string()
+    }
+
+    #[test]
+    fn parses_nested_document() {
+        let value = Value::parse(r#" {"a": [1, -2.5e3, true, null], "b": {"c": "d"}} "#).unwrap();
+        assert_eq!(
+            value.get("b").and_then(|b| b.get("c")),
+            Some(&Value::String("d".into()))
+        );
+        assert_eq!(
+            value.to_string(),
+            r#"{"a":[1,-2.5e3,true,null],"b":{"c":"d"}}"#
+        );
+    }
+
+    #[test]
+    fn large_integers_keep_every_digit() {
+        let a = Value::parse("9007199254740993").unwrap();
+        let b = Value::parse("9007199254740992").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.to_string(), "9007199254740993");
+        let Value::Number(n) = Value::parse("18446744073709551615").unwrap() else {
+            panic!("expected a number");
+        };
+        assert_eq!(n.as_u64(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn numbers_compare_as_decimals() {
+        let eq = |a: &str, b: &str| Value::parse(a).unwrap() == Value::parse(b).unwrap();
+        assert!(eq("1", "1.0"));
+        assert!(eq("100", "1e2"));
+        assert!(eq("0.5", "5E-1"));
+        assert!(eq("0", "-0.0"));
+        assert!(!eq("1", "-1"));
+        assert!(!eq("0.1", "0.10000000000000001"));
+        assert_eq!(canonical("[100, 1.50, -0, 12e+1]"), "[1e2,15e-1,0,12e1]");
+    }
+
+    #[test]
+    fn canonical_sorts_keys() {
+        assert_eq!(
+            canonical(r#"{"b": 1, "a": {"d": 2, "c": 3}}"#),
+            canonical(r#"{"a": {"c": 3, "d": 2.0}, "b": 1}"#)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        for input in [
+            "01", "1.", ".5", "+1", "-", "1e", "1e+", "0x10", "NaN", "Infinity",
+        ] {
+            assert_eq!(Value::parse(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            Value::parse(r#""a\"b\\c\/d\né😀""#),
+            Some(Value::String("a\"b\\c/d\né😀".into()))
+        );
+        for input in [
+            r#""\x""#,
+            r#""\'""#,
+            r#""\u12""#,
+            r#""\ud83d""#,
+            r#""\ude00""#,
+            "\"a\tb\"",
+            r#""abc"#,
+        ] {
+            assert_eq!(Value::parse(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        for input in [
+            "",
+            "[1,]",
+            "{\"a\":1,}",
+            "{a:1}",
+            "[1 2]",
+            "{\"a\" 1}",
+            "tru",
+            "null x",
+            "\u{a0}1",
+        ] {
+            assert_eq!(Value::parse(input), None, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let deep = |n: usize| format!("{}{}", "[".repeat(n), "]".repeat(n));
+        assert!(Value::parse(&deep(MAX_DEPTH)).is_some());
+        assert_eq!(Value::parse(&deep(MAX_DEPTH + 1)), None);
+    }
+
+    #[test]
+    fn display_round_trips_strings() {
+        let original = Value::String("quote \" slash \\ tab \t nul \u{0} bell \u{7}".into());
+        let printed = original.to_string();
+        assert_eq!(
+            printed,
+            r#""quote \" slash \\ tab \t nul \u0000 bell \u0007""#
+        );
+        assert_eq!(Value::parse(&printed), Some(original));
+    }
+}
@@ -0,0 +1,206 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::connection::tcp_connect;
+use crate::dns::resolve;
+use crate::tls::{read_tlv, tls_connect};
+use crate::types::{Error, Hostname, LdapOptions, Result, TcpOptions, TlsOptions, WaitConfig};
+
+const SEQUENCE: u8 = 0x30;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const ENUMERATED: u8 = 0x0a;
+const BIND_REQUEST: u8 = 0x60;
+const BIND_RESPONSE: u8 = 0x61;
+const SIMPLE_AUTH: u8 = 0x80;
+const UNBIND_REQUEST: [u8; 7] = [SEQUENCE, 0x05, INTEGER, 0x01, 0x02, 0x42, 0x00];
+const MAX_RESPONSE: usize = 64 * 1024;
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match u8::try_from(content.len()) {
+        Ok(len) if len < 0x80 => out.push(len),
+        _ => {
+            let len = content.len().to_be_bytes();
+            let skip = len.iter().take_while(|&&b| b == 0).count();
+            out.push(0x80 | (len.len() - skip) as u8);
+            out.extend_from_slice(&len[skip..]);
+        }
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn bind_request(dn: &str, password: &str) -> Vec<u8> {
+    let bind = [
+        tlv(INTEGER, &[3]),
+        tlv(OCTET_STRING, dn.as_bytes()),
+        tlv(SIMPLE_AUTH, password.as_bytes()),
+    ]
+    .concat();
+    tlv(
+        SEQUENCE,
+        &[tlv(INTEGER, &[1]), tlv(BIND_REQUEST, &bind)].concat(),
+    )
+}
+
+fn parse_bind_response(message: &[u8]) -> Option<(u32, String)> {
+    let (tag, body, _) = read_tlv(message)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (_, _, rest) = read_tlv(body)?;
+    let (tag, op, _) = read_tlv(rest)?;
+    if tag != BIND_RESPONSE {
+        return None;
+    }
+    let (tag, code, rest) = read_tlv(op)?;
+    if tag != ENUMERATED || code.is_empty() || code.len() > 4 {
+        return None;
+    }
+    let code = code.iter().fold(0, |acc, &b| (acc << 8) | u32::from(b));
+    let (_, _, rest) = read_tlv(rest)?;
+    let diagnostic = read_tlv(rest)
+        .map(|(_, text, _)| String::from_utf8_lossy(text).into_owned())
+        .unwrap_or_default();
+    Some((code, diagnostic))
+}
+
+async fn bind<S>(stream: &mut S, options: &LdapOptions) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let io_error = |e: std::io::Error| Error::Connection(format!("LDAP bind failed: {e}"));
+    let request = bind_request(
+        options.bind_dn.as_deref().unwrap_or_default(),
+        options.password.as_deref().unwrap_or_default(),
+    );
+    stream.write_all(&request).await.map_err(io_error)?;
+
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let message = loop {
+        if let Some((_, _, rest)) = read_tlv(&response) {
+            break &response[..response.len() - rest.len()];
+        }
+        if response.len() > MAX_RESPONSE {
+            return Err(Error::Response(
+                "LDAP bind response is too large".to_string(),
+            ));
+        }
+        let read = stream.read(&mut chunk).await.map_err(io_error)?;
+        if read == 0 {
+            return Err(Error::Connection(
+                "LDAP server closed the connection during bind".to_string(),
+            ));
+        }
+        response.extend_from_slice(&chunk[..read]);
+    };
+
+    let (code, diagnostic) = parse_bind_response(message)
+        .ok_or_else(|| Error::Response("Malformed LDAP bind response".to_string()))?;
+    let _ = stream.write_all(&UNBIND_REQUEST).await;
+    match (code, diagnostic.is_empty()) {
+        (0, _) => Ok(()),
+        (code, true) => Err(Error::Response(format!(
+            "LDAP bind failed with result code {code}"
+        ))),
+        (code, false) => Err(Error::Response(format!(
+            "LDAP bind failed with result code {code}: {diagnostic}"
+        ))),
+    }
+}
+
+pub async fn ldap_bind(
+    host: &Hostname,
+    port: u16,
+    tls: Option<&TlsOptions>,
+    options: &LdapOptions,
+    config: &WaitConfig,
+) -> Result<()> {
+    match tls {
+        Some(tls) => {
+            let mut stream = tls_connect(host, port, tls, config).await?;
+            bind(&mut stream, options).await
+        }
+        None => {
+            let addrs = resolve(host, port, &config.dns_servers).await?;
+            let mut stream = tcp_connect(&addrs, TcpOptions::default(), config).await?;
+            bind(&mut stream, options).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_simple_bind_requests() {
+        assert_eq!(
+            bind_request("cn=admin", "pw"),
+            [
+                &[SEQUENCE, 0x16, INTEGER, 0x01, 0x01, BIND_REQUEST, 0x11][..],
+                &[INTEGER, 0x01, 0x03, OCTET_STRING, 0x08],
+                b"cn=admin",
+                &[SIMPLE_AUTH, 0x02],
+                b"pw",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn long_lengths_round_trip() {
+        for len in [0x7f, 0x80, 0xff, 0x100, 0x1_0000] {
+            let content = vec![b'x'; len];
+            let encoded = tlv(OCTET_STRING, &content);
+            let (tag, value, rest) = read_tlv(&encoded).unwrap();
+            assert_eq!((tag, value.len(), rest.len()), (OCTET_STRING, len, 0));
+        }
+        assert_eq!(
+            tlv(OCTET_STRING, &[0; 0x80])[..3],
+            [OCTET_STRING, 0x81, 0x80]
+        );
+    }
+
+    #[test]
+    fn parses_bind_responses() {
+        let success = [
+            SEQUENCE,
+            0x0c,
+            INTEGER,
+            0x01,
+            0x01,
+            BIND_RESPONSE,
+            0x07,
+            ENUMERATED,
+            0x01,
+            0x00,
+            OCTET_STRING,
+            0x00,
+            OCTET_STRING,
+            0x00,
+        ];
+        assert_eq!(parse_bind_response(&success), Some((0, String::new())));
+
+        let op = [
+            tlv(ENUMERATED, &[49]),
+            tlv(OCTET_STRING, b""),
+            tlv(OCTET_STRING, b"invalid credentials"),
+        ]
+        .concat();
+        let refused = tlv(
+            SEQUENCE,
+            &[tlv(INTEGER, &[1]), tlv(BIND_RESPONSE, &op)].concat(),
+        );
+        assert_eq!(
+            parse_bind_response(&refused),
+            Some((49, "invalid credentials".to_string()))
+        );
+
+        assert_eq!(parse_bind_response(&success[..success.len() - 1]), None);
+        let mut other_op = success;
+        other_op[5] = 0x65;
+        assert_eq!(parse_bind_response(&other_op), None);
+    }
+}
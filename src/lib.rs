@@ -0,0 +1,240 @@
+mod checker;
+mod cli;
+mod command;
+mod connection;
+#[cfg(feature = "consul")]
+mod consul;
+mod dns;
+mod etcd;
+mod handle;
+mod human;
+mod json;
+mod ldap;
+mod log;
+mod mount;
+mod nats;
+mod plan;
+mod presets;
+#[cfg(target_os = "linux")]
+mod process;
+mod profile;
+pub mod report;
+mod s3;
+#[cfg(feature = "server")]
+mod server;
+mod ssh;
+mod tls;
+mod types;
+mod vault;
+mod zookeeper;
+
+pub use checker::{AsyncTargetChecker, CheckFuture, register_checker};
+pub use connection::{wait_for_targets, wait_until};
+pub use handle::{WaitHandle, spawn_wait};
+pub use plan::{Phase, PhaseResult, WaitPlan};
+#[cfg(feature = "server")]
+pub use server::serve_readiness;
+pub use types::{
+    CancelReason, Error, HostPort, Hostname, Pending, Port, ProgressReporter, Quorum, Reporter,
+    Result, RetryDecision, Target, TargetError, TargetFailure, TargetGroup, TargetOptions,
+    TargetResult, TargetResultSliceExt, TargetState, TargetStatus, TcpTargetBuilder, WaitConfig,
+    WaitOutcome, WaitResult,
+};
+
+// The waitup CLI; returns the exit code. Checkers registered beforehand
+// handle their schemes on the command line too.
+pub use cli::run;
+
+use std::sync::Arc;
+
+use types::{DurationBounds, parse_duration};
+
+// For scripts: parse the targets as the CLI would and wait with its defaults.
+pub async fn wait<I, S>(targets: I, timeout: &str) -> Result<WaitResult>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let options = TargetOptions::default();
+    let targets = targets
+        .into_iter()
+        .map(|target| Target::parse(target.as_ref(), &options))
+        .collect::<Result<Vec<_>>>()?;
+    if targets.is_empty() {
+        return Err(Error::Config(
+            "At least one target must be specified".to_string(),
+        ));
+    }
+    let config = Arc::new(WaitConfig {
+        overall_timeout: parse_duration(timeout, "timeout", DurationBounds::TIMEOUT)?,
+        ..WaitConfig::default()
+    });
+    let result = wait_for_targets(targets, &config).await;
+    match result.error {
+        Some(e) => Err(e),
+        None => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn wait_for_listening_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let result = wait([format!("{addr}#team=core")], "5s").await.unwrap();
+        assert_eq!(result.targets.len(), 1);
+        assert!(result.targets[0].is_success());
+        let (ready, failed) = result.targets.partition_by_success();
+        assert_eq!((ready.len(), failed.len()), (1, 0));
+        assert_eq!(result.targets.group_by_kind()["tcp"].len(), 1);
+        assert_eq!(result.targets.group_by_tag("team")["core"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn wait_rejects_bad_input() {
+        assert!(matches!(
+            wait(["db:5432"], "soon").await,
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(wait(["db:0"], "5s").await, Err(Error::Config(_))));
+        assert!(matches!(
+            wait(Vec::<&str>::new(), "5s").await,
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn wait_times_out() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let error = wait([addr.as_str()], "300ms").await.unwrap_err();
+        assert_eq!(error.code(), "TIMEOUT");
+    }
+
+    #[tokio::test]
+    async fn fatal_failure_ends_an_all_of_wait() {
+        // Answers every query with NXDOMAIN.
+        let dns = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = dns.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, peer)) = dns.recv_from(&mut buf).await {
+                let mut reply = buf[..len].to_vec();
+                reply[2..4].copy_from_slice(&[0x81, 0x83]);
+                let _ = dns.send_to(&reply, peer).await;
+            }
+        });
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_addr = closed.local_addr().unwrap().to_string();
+        drop(closed);
+
+        let options = TargetOptions::default();
+        let targets = ["missing.test:5432", closed_addr.as_str()]
+            .map(|spec| Target::parse(spec, &options).unwrap());
+        let config = Arc::new(WaitConfig {
+            overall_timeout: std::time::Duration::from_secs(20),
+            initial_interval: std::time::Duration::from_millis(50),
+            dns_failure: types::DnsFailure::Fail,
+            dns_servers: vec![server],
+            ..WaitConfig::default()
+        });
+        let result = wait_for_targets(targets, &config).await;
+        assert!(result.elapsed < std::time::Duration::from_secs(5));
+        assert_eq!(result.error.unwrap().code(), "DNS_FAILURE");
+        assert!(matches!(
+            result.targets[1].error,
+            Some(TargetError::Stopped(CancelReason::DependencyFailed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn timeout_while_not_yet_stable() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let target = Target::parse(&addr, &TargetOptions::default()).unwrap();
+        let config = Arc::new(WaitConfig {
+            overall_timeout: std::time::Duration::from_millis(300),
+            initial_interval: std::time::Duration::from_millis(50),
+            stable_for: Some(std::time::Duration::from_secs(10)),
+            ..WaitConfig::default()
+        });
+        let result = wait_for_targets([target], &config).await;
+        let Some(Error::Timeout { targets }) = result.error else {
+            panic!("expected a timeout, got {:?}", result.error);
+        };
+        assert!(targets[0].last_error.is_none());
+        assert!(matches!(
+            targets[0].pending,
+            Some(Pending::Stability { .. })
+        ));
+        assert_eq!(targets[0].attempts, result.targets[0].attempts);
+        assert_eq!(
+            targets[0].reason().unwrap().split(" / ").nth(1),
+            Some("10s")
+        );
+        assert!(targets[0].to_string().contains(", not stable for "));
+    }
+
+    #[test]
+    fn host_port_parses_from_the_crate_root() {
+        let addr: crate::HostPort = "[::1]:8080".parse().unwrap();
+        assert_eq!(
+            addr.host,
+            crate::Hostname::Ip(std::net::Ipv6Addr::LOCALHOST.into())
+        );
+        assert_eq!(addr.port.get(), 8080);
+    }
+
+    #[test]
+    fn tcp_builder_is_public() {
+        let host = crate::Hostname::new("db").unwrap();
+        let builder: crate::TcpTargetBuilder = Target::tcp_builder(host, crate::Port::POSTGRES);
+        let target = builder.nodelay(true).build();
+        assert_eq!(target.to_string(), "db:5432");
+    }
+
+    #[test]
+    fn well_known_ports_are_public() {
+        assert_eq!(crate::Port::POSTGRES.get(), 5432);
+        assert_eq!("redis".parse::<crate::Port>().unwrap(), crate::Port::REDIS);
+    }
+
+    #[tokio::test]
+    async fn wait_until_retries_a_predicate() {
+        let calls = std::sync::atomic::AtomicU32::new(0);
+        let config = WaitConfig {
+            overall_timeout: std::time::Duration::from_secs(5),
+            initial_interval: std::time::Duration::from_millis(10),
+            ..WaitConfig::default()
+        };
+        wait_until(
+            || async {
+                match calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0..3 => Err(Error::Connection("not yet".to_string())),
+                    _ => Ok(()),
+                }
+            },
+            &config,
+        )
+        .await
+        .unwrap();
+        assert_eq!(calls.into_inner(), 4);
+
+        let config = WaitConfig {
+            overall_timeout: std::time::Duration::from_millis(100),
+            ..config
+        };
+        let error = wait_until(
+            || async { Err(Error::Connection("never".to_string())) },
+            &config,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.code(), "TIMEOUT");
+        assert!(error.to_string().contains("condition"), "{error}");
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::{self, Write as _};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::types::{Error, Result};
+
+const CRATE: &str = env!("CARGO_CRATE_NAME");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+}
+
+impl LogLevel {
+    const fn level(self) -> Level {
+        match self {
+            Self::Debug => Level::DEBUG,
+            Self::Info => Level::INFO,
+            Self::Warn => Level::WARN,
+        }
+    }
+}
+
+fn parse_level(raw: &str) -> Option<Level> {
+    raw.parse().ok()
+}
+
+fn is_target(target: &str) -> bool {
+    !target.is_empty()
+        && target
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':'))
+}
+
+fn within(target: &str, prefix: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+// RUST_LOG-style directives, e.g. "warn,waitup::dns=debug,hyper_util=trace".
+// The longest matching target wins; a bare level applies to every target.
+// --log-level only covers waitup's own events. Span and field filters
+// ("target[span]=level") are rejected rather than silently never matching.
+#[derive(Debug, Default)]
+struct Filter {
+    crate_level: Option<Level>,
+    default: Option<Level>,
+    directives: Vec<(String, Level)>,
+}
+
+impl Filter {
+    fn new(level: Option<LogLevel>, spec: &str) -> Result<Self> {
+        let mut filter = Self {
+            crate_level: level.map(LogLevel::level),
+            ..Self::default()
+        };
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let invalid = || Error::Config(format!("Invalid log directive '{directive}'"));
+            match directive.split_once('=') {
+                Some((target, level)) if is_target(target) => {
+                    let level = parse_level(level).ok_or_else(invalid)?;
+                    filter.directives.push((target.to_string(), level));
+                }
+                Some(_) => return Err(invalid()),
+                None => filter.default = Some(parse_level(directive).ok_or_else(invalid)?),
+            }
+        }
+        Ok(filter)
+    }
+
+    fn max_level(&self, target: &str) -> Option<Level> {
+        self.directives
+            .iter()
+            .filter(|(prefix, _)| within(target, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .or(self.crate_level.filter(|_| within(target, CRATE)))
+            .or(self.default)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.max_level(metadata.target())
+            .is_some_and(|max| *metadata.level() <= max)
+    }
+}
+
+#[derive(Default)]
+struct Line {
+    message: String,
+    fields: String,
+}
+
+impl Visit for Line {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+// One line per event on stderr; spans are not used.
+struct StderrLogger {
+    filter: Filter,
+}
+
+impl Subscriber for StderrLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut line = Line::default();
+        event.record(&mut line);
+        let level = metadata.level().as_str().to_ascii_lowercase();
+        if within(metadata.target(), CRATE) {
+            eprintln!("[{level}] {}{}", line.message, line.fields);
+        } else {
+            eprintln!(
+                "[{level}] {}: {}{}",
+                metadata.target(),
+                line.message,
+                line.fields
+            );
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+// Nothing is logged unless --log-level or WAITUP_LOG is given.
+pub fn init(level: Option<LogLevel>) -> Result<()> {
+    let spec = std::env::var("WAITUP_LOG").unwrap_or_default();
+    if level.is_none() && spec.trim().is_empty() {
+        return Ok(());
+    }
+    let filter = Filter::new(level, &spec)?;
+    // Fails if the program embedding waitup installed its own subscriber,
+    // which then keeps receiving the events.
+    let _ = tracing::subscriber::set_global_default(StderrLogger { filter });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn max(filter: &Filter, target: &str) -> Option<Level> {
+        filter.max_level(target)
+    }
+
+    #[test]
+    fn log_level_covers_only_this_crate() {
+        let filter = Filter::new(Some(LogLevel::Info), "").unwrap();
+        assert_eq!(max(&filter, "waitup::connection"), Some(Level::INFO));
+        assert_eq!(max(&filter, "waitup"), Some(Level::INFO));
+        assert_eq!(max(&filter, "hyper_util::client"), None);
+        assert_eq!(max(&filter, "waitupx"), None);
+    }
+
+    #[test]
+    fn longest_directive_wins() {
+        let filter =
+            Filter::new(Some(LogLevel::Warn), "error,hyper=info,waitup::dns=debug").unwrap();
+        assert_eq!(max(&filter, "waitup::dns"), Some(Level::DEBUG));
+        assert_eq!(max(&filter, "waitup::connection"), Some(Level::WARN));
+        assert_eq!(max(&filter, "hyper::proto"), Some(Level::INFO));
+        assert_eq!(max(&filter, "rustls::client"), Some(Level::ERROR));
+    }
+
+    #[test]
+    fn invalid_directives() {
+        for spec in [
+            "loud",
+            "off",
+            "=debug",
+            "waitup=",
+            "waitup=verbose",
+            "waitup=debug=trace",
+            "waitup[probe]=debug",
+            "waitup{attempt=1}=debug",
+            "waitup dns=debug",
+            "info,waitup::dns=loud",
+        ] {
+            assert!(
+                matches!(Filter::new(None, spec), Err(Error::Config(_))),
+                "accepted {spec:?}"
+            );
+        }
+        let Err(e) = Filter::new(None, "info, waitup=loud ,warn") else {
+            panic!("accepted a bad level");
+        };
+        assert_eq!(e.to_string(), "Invalid log directive 'waitup=loud'");
+        assert!(Filter::new(None, " , ").is_ok());
+        assert!(Filter::new(None, "DEBUG,hyper-util::client=Trace").is_ok());
+    }
+}
@@ -1,8 +1,4 @@
-mod cli;
-mod connection;
-mod types;
-
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    std::process::exit(cli::run().await);
+    std::process::exit(waitup::run().await);
 }
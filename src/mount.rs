@@ -0,0 +1,237 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::types::{Error, Result};
+
+#[derive(Debug, PartialEq, Eq)]
+struct MountEntry {
+    mount_point: PathBuf,
+    fstype: String,
+}
+
+// mountinfo escapes space, tab, newline and backslash as \ooo.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| {
+            std::str::from_utf8(digits)
+                .ok()
+                .and_then(|d| u8::from_str_radix(d, 8).ok())
+        });
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw
+fn parse_mountinfo_line(line: &str) -> Option<MountEntry> {
+    let (fields, rest) = line.split_once(" - ")?;
+    let mount_point = fields.split(' ').nth(4)?;
+    let fstype = rest.split(' ').next().filter(|t| !t.is_empty())?;
+    Some(MountEntry {
+        mount_point: PathBuf::from(unescape(mount_point)),
+        fstype: unescape(fstype),
+    })
+}
+
+// The last entry wins: a later mount on the same point hides earlier ones.
+fn find_mount(mountinfo: &str, path: &Path) -> Option<MountEntry> {
+    mountinfo
+        .lines()
+        .rev()
+        .filter_map(parse_mountinfo_line)
+        .find(|entry| entry.mount_point == path)
+}
+
+// Whether `path` is a mount point, and its filesystem type when it can be told.
+enum MountState {
+    Mounted(Option<String>),
+    NotMounted,
+}
+
+#[cfg(target_os = "linux")]
+fn mount_state(path: &Path) -> Result<MountState> {
+    let Ok(mountinfo) = fs::read_to_string("/proc/self/mountinfo") else {
+        return statfs_mount_state(path);
+    };
+    let path = fs::canonicalize(path)
+        .map_err(|e| Error::Connection(format!("Cannot resolve {}: {e}", path.display())))?;
+    Ok(match find_mount(&mountinfo, &path) {
+        Some(entry) => MountState::Mounted(Some(entry.fstype)),
+        None => MountState::NotMounted,
+    })
+}
+
+// Without /proc (e.g. a chroot), statfs names the filesystem and a device
+// change against the parent directory marks the mount point. That misses
+// bind mounts from the same filesystem.
+#[cfg(target_os = "linux")]
+fn statfs_mount_state(path: &Path) -> Result<MountState> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let shown = path.display();
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::Config(format!("{shown} contains a NUL byte")))?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &raw mut stat) } != 0 {
+        return Err(Error::Connection(format!(
+            "statfs on {shown} failed: {}",
+            io::Error::last_os_error()
+        )));
+    }
+    // f_type is narrower than i64 on some targets.
+    #[allow(clippy::useless_conversion)]
+    let magic = i64::from(stat.f_type);
+    Ok(if device_changes(path)? {
+        MountState::Mounted(fs_magic_name(magic).map(str::to_string))
+    } else {
+        MountState::NotMounted
+    })
+}
+
+#[cfg(target_os = "linux")]
+const fn fs_magic_name(magic: i64) -> Option<&'static str> {
+    Some(match magic {
+        0xEF53 => "ext4",
+        0x5846_5342 => "xfs",
+        0x9123_683E => "btrfs",
+        0x0102_1994 => "tmpfs",
+        0x794C_7630 => "overlay",
+        0x6969 => "nfs",
+        0xFF53_4D42 => "cifs",
+        0x6573_5546 => "fuse",
+        _ => return None,
+    })
+}
+
+// Other platforms have no mount table we read; a device change against the
+// parent directory is taken as the mount point, and the type is unknown.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn mount_state(path: &Path) -> Result<MountState> {
+    Ok(if device_changes(path)? {
+        MountState::Mounted(None)
+    } else {
+        MountState::NotMounted
+    })
+}
+
+// Windows: only writability is checked.
+#[cfg(not(unix))]
+fn mount_state(_path: &Path) -> Result<MountState> {
+    Ok(MountState::Mounted(None))
+}
+
+#[cfg(unix)]
+fn device_changes(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let shown = path.display();
+    let metadata =
+        fs::metadata(path).map_err(|e| Error::Connection(format!("Cannot access {shown}: {e}")))?;
+    let parent = fs::metadata(path.join(".."))
+        .map_err(|e| Error::Connection(format!("Cannot access parent of {shown}: {e}")))?;
+    let is_root = metadata.dev() == parent.dev() && metadata.ino() == parent.ino();
+    Ok(metadata.dev() != parent.dev() || is_root)
+}
+
+pub fn check_mount(path: &Path, fstype: Option<&str>) -> Result<()> {
+    let shown = path.display();
+    let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::Connection(format!("{shown} does not exist")),
+        _ => Error::Connection(format!("Cannot access {shown}: {e}")),
+    })?;
+    if !metadata.is_dir() {
+        return Err(Error::Config(format!("{shown} is not a directory")));
+    }
+
+    match (mount_state(path)?, fstype) {
+        (MountState::NotMounted, _) => {
+            return Err(Error::Connection(format!("{shown} is not mounted yet")));
+        }
+        (MountState::Mounted(Some(actual)), Some(expected)) if actual != expected => {
+            return Err(Error::Connection(format!(
+                "{shown} is a {actual} mount, waiting for {expected}"
+            )));
+        }
+        (MountState::Mounted(None), Some(expected)) => {
+            return Err(Error::Config(format!(
+                "Cannot tell the filesystem type of {shown} on this system (wanted {expected})"
+            )));
+        }
+        (MountState::Mounted(_), _) => {}
+    }
+
+    let probe = path.join(format!(".waitup-probe-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map_err(|e| Error::Connection(format!("{shown} is not writable: {e}")))?;
+    fs::remove_file(&probe)
+        .map_err(|e| Error::Connection(format!("Cannot remove probe file in {shown}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 0:25 / /data rw,relatime shared:2 - nfs4 server:/export rw,vers=4.2
+31 22 8:1 /srv/shared /mnt/bind rw,relatime shared:1 - ext4 /dev/sda1 rw
+32 22 0:26 / /mnt/with\\040space rw - tmpfs tmpfs rw
+33 30 0:27 / /data rw,relatime - tmpfs tmpfs rw
+34 22 0:28 / /opt/tagged rw master:3 propagate_from:1 - xfs /dev/sdb1 rw
+";
+
+    fn fstype(path: &str) -> Option<String> {
+        find_mount(MOUNTINFO, Path::new(path)).map(|entry| entry.fstype)
+    }
+
+    #[test]
+    fn finds_exact_mount_point() {
+        assert_eq!(fstype("/").as_deref(), Some("ext4"));
+        assert_eq!(fstype("/opt/tagged").as_deref(), Some("xfs"));
+    }
+
+    #[test]
+    fn subdirectory_is_not_a_mount_point() {
+        assert_eq!(fstype("/data/sub"), None);
+        assert_eq!(fstype("/mnt"), None);
+    }
+
+    #[test]
+    fn bind_mount_from_same_filesystem() {
+        assert_eq!(fstype("/mnt/bind").as_deref(), Some("ext4"));
+    }
+
+    #[test]
+    fn later_mount_hides_earlier_one() {
+        assert_eq!(fstype("/data").as_deref(), Some("tmpfs"));
+    }
+
+    #[test]
+    fn octal_escapes_in_mount_point() {
+        assert_eq!(fstype("/mnt/with space").as_deref(), Some("tmpfs"));
+        assert_eq!(unescape("a\\134b\\011c"), "a\\b\tc");
+        assert_eq!(unescape("trailing\\04"), "trailing\\04");
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        assert_eq!(parse_mountinfo_line("22 1 8:1 / /"), None);
+        assert_eq!(parse_mountinfo_line("22 1 - "), None);
+    }
+}
@@ -0,0 +1,65 @@
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::connection::tcp_connect;
+use crate::dns::resolve;
+use crate::json::Value;
+use crate::types::{Error, Hostname, NatsOptions, Result, TcpOptions, WaitConfig};
+
+const CONNECT: &[u8] =
+    b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"waitup\"}\r\nPING\r\n";
+const MAX_LINES: usize = 8;
+
+pub async fn nats_handshake(
+    host: &Hostname,
+    port: u16,
+    tcp: TcpOptions,
+    options: NatsOptions,
+    config: &WaitConfig,
+) -> Result<()> {
+    let addrs = resolve(host, port, &config.dns_servers).await?;
+    let mut stream = tcp_connect(&addrs, tcp, config).await?;
+    let (reader, mut writer) = stream.split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut next_line = async || {
+        lines
+            .next_line()
+            .await
+            .map_err(|e| Error::Connection(format!("NATS handshake failed: {e}")))?
+            .ok_or_else(|| Error::Connection("NATS server closed the connection".to_string()))
+    };
+
+    let banner = next_line().await?;
+    let info = banner
+        .strip_prefix("INFO ")
+        .and_then(|json| Value::parse(json.trim()))
+        .ok_or_else(|| Error::Response(format!("Expected NATS INFO banner, got '{banner}'")))?;
+    if !options.ping {
+        return Ok(());
+    }
+    if info.get("tls_required") == Some(&Value::Bool(true)) {
+        return Err(Error::Config(format!(
+            "--nats-ping needs a plain-text connection, but {host}:{port} requires TLS"
+        )));
+    }
+
+    writer
+        .write_all(CONNECT)
+        .await
+        .map_err(|e| Error::Connection(format!("NATS handshake failed: {e}")))?;
+    for _ in 0..MAX_LINES {
+        let line = next_line().await?;
+        match line.trim_end() {
+            "PONG" => return Ok(()),
+            error if error.starts_with("-ERR") => {
+                return Err(Error::Response(format!(
+                    "NATS server rejected CONNECT: {}",
+                    error[4..].trim()
+                )));
+            }
+            _ => {}
+        }
+    }
+    Err(Error::Response(
+        "NATS server did not answer PING".to_string(),
+    ))
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::connection::wait_for_targets;
+use crate::types::{Error, Result, TargetGroup, WaitConfig, WaitResult};
+
+#[derive(Debug)]
+pub struct Phase {
+    pub name: String,
+    pub groups: Vec<TargetGroup>,
+    pub config: WaitConfig,
+}
+
+impl Phase {
+    // Groups are checked side by side; the phase needs every group's quorum.
+    async fn run(&self) -> WaitResult {
+        if let [group] = self.groups.as_slice() {
+            let targets = group.targets.iter().cloned();
+            return wait_for_targets(targets, &group_config(group, &self.config)).await;
+        }
+        let mut set = JoinSet::new();
+        for (index, group) in self.groups.iter().enumerate() {
+            let targets = group.targets.clone();
+            let config = group_config(group, &self.config);
+            set.spawn(async move { (index, wait_for_targets(targets, &config).await) });
+        }
+        let mut results = set.join_all().await;
+        results.sort_by_key(|&(index, _)| index);
+
+        let mut merged = WaitResult {
+            targets: Vec::new(),
+            elapsed: Duration::ZERO,
+            error: None,
+        };
+        for (_, result) in results {
+            merged.targets.extend(result.targets);
+            merged.elapsed = merged.elapsed.max(result.elapsed);
+            merged.error = merged.error.or(result.error);
+        }
+        merged
+    }
+}
+
+fn group_config(group: &TargetGroup, config: &WaitConfig) -> Arc<WaitConfig> {
+    Arc::new(WaitConfig {
+        quorum: group.quorum,
+        ..config.clone()
+    })
+}
+
+#[derive(Debug)]
+pub struct PhaseResult {
+    pub name: String,
+    pub result: WaitResult,
+}
+
+#[derive(Debug, Default)]
+pub struct WaitPlan {
+    phases: Vec<Phase>,
+}
+
+impl WaitPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn phase(
+        mut self,
+        name: impl Into<String>,
+        groups: Vec<TargetGroup>,
+        config: WaitConfig,
+    ) -> Self {
+        self.phases.push(Phase {
+            name: name.into(),
+            groups,
+            config,
+        });
+        self
+    }
+
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        for phase in &mut self.phases {
+            phase.config.deadline = Some(deadline);
+        }
+    }
+
+    pub async fn run(&self) -> Vec<PhaseResult> {
+        let mut results = Vec::with_capacity(self.phases.len());
+        for phase in &self.phases {
+            let result = phase.run().await;
+            let failed = !result.is_success();
+            results.push(PhaseResult {
+                name: phase.name.clone(),
+                result,
+            });
+            if failed {
+                break;
+            }
+        }
+        results
+    }
+}
+
+pub fn into_result(results: Vec<PhaseResult>) -> Result<()> {
+    let single = results.len() == 1;
+    for phase in results {
+        match phase.result.error {
+            Some(e) if single => return Err(e),
+            Some(e) => {
+                return Err(Error::Phase {
+                    name: phase.name,
+                    source: Box::new(e),
+                });
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use crate::types::{Error, Result};
+
+const CLICKHOUSE_HTTP_PORT: u16 = 8123;
+const CLICKHOUSE_NATIVE_PORT: u16 = 9000;
+
+fn clickhouse(spec: &str, rest: &str, tags: &str) -> Result<Vec<String>> {
+    let invalid = |reason: &str| {
+        Error::Config(format!(
+            "Invalid ClickHouse target '{spec}': {reason} (expected clickhouse://host[?http=PORT&native=PORT])"
+        ))
+    };
+    let (host, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let host = host.strip_suffix('/').unwrap_or(host);
+    let has_port = if host.starts_with('[') {
+        !host.ends_with(']')
+    } else {
+        host.contains(':')
+    };
+    if host.is_empty() || host.contains('/') || has_port {
+        return Err(invalid("ports go in the query string"));
+    }
+
+    let (mut http, mut native) = (CLICKHOUSE_HTTP_PORT, CLICKHOUSE_NATIVE_PORT);
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| invalid("malformed query"))?;
+        let port = value
+            .parse()
+            .map_err(|_| invalid(&format!("invalid port '{value}'")))?;
+        match key {
+            "http" => http = port,
+            "native" => native = port,
+            _ => return Err(invalid(&format!("unknown parameter '{key}'"))),
+        }
+    }
+
+    Ok(vec![
+        format!("http://{host}:{http}/ping{tags}"),
+        format!("{host}:{native}{tags}"),
+    ])
+}
+
+pub fn expand_target(spec: &str) -> Result<Vec<String>> {
+    let (body, tags) = spec.find('#').map_or((spec, ""), |at| spec.split_at(at));
+    if let Some(rest) = body.strip_prefix("clickhouse://") {
+        return clickhouse(spec, rest, tags);
+    }
+    Ok(vec![spec.to_string()])
+}
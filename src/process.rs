@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::types::{Error, ProcessOptions, ProcessSelector, Result};
+
+fn read_pid(path: &Path) -> Result<u32> {
+    let shown = path.display();
+    let content = fs::read_to_string(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Error::Connection(format!("{shown} does not exist")),
+        _ => Error::Connection(format!("Failed to read {shown}: {e}")),
+    })?;
+    content
+        .trim()
+        .parse()
+        .map_err(|_| Error::Connection(format!("{shown} does not contain a PID")))
+}
+
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).is_dir()
+}
+
+fn matches_name(pid: u32, name: &str) -> bool {
+    let proc_dir = Path::new("/proc").join(pid.to_string());
+    let comm = fs::read_to_string(proc_dir.join("comm")).unwrap_or_default();
+    if comm.trim_end() == name {
+        return true;
+    }
+    fs::read(proc_dir.join("cmdline")).is_ok_and(|cmdline| {
+        let argv0 = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+        let argv0 = String::from_utf8_lossy(argv0);
+        Path::new(argv0.as_ref())
+            .file_name()
+            .is_some_and(|f| f == name)
+    })
+}
+
+fn find_by_name(name: &str) -> Result<Vec<u32>> {
+    let entries =
+        fs::read_dir("/proc").map_err(|e| Error::Config(format!("Cannot list processes: {e}")))?;
+    Ok(entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|pid| pid.parse().ok())
+        .filter(|&pid| pid != std::process::id() && matches_name(pid, name))
+        .collect())
+}
+
+fn age(pid: u32) -> Option<Duration> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    let start_ticks: u64 = fields.split_whitespace().nth(19)?.parse().ok()?;
+    let uptime: f64 = fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    // SAFETY: sysconf has no preconditions.
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+    let started = start_ticks as f64 / ticks_per_sec as f64;
+    Some(Duration::from_secs_f64((uptime - started).max(0.0)))
+}
+
+pub fn check_process(selector: &ProcessSelector, options: &ProcessOptions) -> Result<()> {
+    let pids = match selector {
+        ProcessSelector::Name(name) => {
+            let pids = find_by_name(name)?;
+            if pids.is_empty() {
+                return Err(Error::Connection(format!("No process named '{name}'")));
+            }
+            pids
+        }
+        ProcessSelector::PidFile(path) => {
+            let pid = read_pid(path)?;
+            if !is_running(pid) {
+                return Err(Error::Connection(format!(
+                    "Process {pid} from {} is not running",
+                    path.display()
+                )));
+            }
+            vec![pid]
+        }
+    };
+
+    let Some(min_age) = options.min_age else {
+        return Ok(());
+    };
+    let (pid, oldest) = pids
+        .iter()
+        .filter_map(|&pid| Some((pid, age(pid)?)))
+        .max_by_key(|&(_, age)| age)
+        .ok_or_else(|| Error::Connection(format!("{selector} exited while being checked")))?;
+    if oldest < min_age {
+        return Err(Error::Response(format!(
+            "Process {pid} has been running for {}, need {}",
+            humantime::format_duration(Duration::from_secs(oldest.as_secs())),
+            humantime::format_duration(min_age)
+        )));
+    }
+    Ok(())
+}
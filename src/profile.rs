@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::types::{Error, Result};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+pub type Profile = HashMap<String, String>;
+
+// One configuration layer; unset fields fall through to the layer below.
+#[derive(Debug, Default)]
+pub struct PartialWaitConfig {
+    pub timeout: Option<String>,
+    pub target_timeout: Option<String>,
+    pub interval: Option<String>,
+    pub connection_timeout: Option<String>,
+    pub user_agent: Option<String>,
+    pub targets: Option<Vec<String>>,
+}
+
+impl PartialWaitConfig {
+    // Values set in `other` win.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            timeout: other.timeout.or(self.timeout),
+            target_timeout: other.target_timeout.or(self.target_timeout),
+            interval: other.interval.or(self.interval),
+            connection_timeout: other.connection_timeout.or(self.connection_timeout),
+            user_agent: other.user_agent.or(self.user_agent),
+            targets: other.targets.or(self.targets),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Preset {
+    Docker,
+    Kubernetes,
+    Ci,
+    Local,
+}
+
+impl Preset {
+    const fn settings(self) -> (&'static str, &'static str, &'static str) {
+        // (timeout, interval, connection-timeout)
+        match self {
+            Self::Docker => ("60s", "1s", "5s"),
+            Self::Kubernetes => ("5m", "2s", "10s"),
+            Self::Ci => ("2m", "500ms", "5s"),
+            Self::Local => ("10s", "250ms", "1s"),
+        }
+    }
+
+    pub fn layer(self) -> PartialWaitConfig {
+        let (timeout, interval, connection_timeout) = self.settings();
+        PartialWaitConfig {
+            timeout: Some(timeout.to_string()),
+            interval: Some(interval.to_string()),
+            connection_timeout: Some(connection_timeout.to_string()),
+            ..PartialWaitConfig::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProfileFile {
+    profiles: HashMap<String, Profile>,
+}
+
+impl ProfileFile {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::Config(format!("Cannot read config file '{}': {e}", path.display()))
+        })?;
+        Self::parse(&content)
+            .map_err(|e| Error::Config(format!("Invalid config file '{}': {e}", path.display())))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut file = Self::default();
+        let mut current: Option<String> = None;
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            let lineno = index + 1;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[') {
+                let name = section
+                    .strip_suffix(']')
+                    .and_then(|s| s.trim().strip_prefix("profile."))
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| {
+                        Error::Config(format!("line {lineno}: expected [profile.<name>]"))
+                    })?;
+                file.profiles.entry(name.to_string()).or_default();
+                current = Some(name.to_string());
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| Error::Config(format!("line {lineno}: expected key = value")))?;
+            let name = current.as_ref().ok_or_else(|| {
+                Error::Config(format!("line {lineno}: key outside of a [profile.<name>]"))
+            })?;
+            let value = parse_value(value.trim())
+                .ok_or_else(|| Error::Config(format!("line {lineno}: invalid value")))?;
+            file.profiles
+                .entry(name.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value);
+        }
+
+        Ok(file)
+    }
+
+    pub fn profile(&self, name: &str) -> Result<Profile> {
+        let mut chain = Vec::new();
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if chain.contains(&name) {
+                return Err(Error::Config(format!(
+                    "Profile inheritance cycle: {} -> {name}",
+                    chain.join(" -> ")
+                )));
+            }
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| Error::Config(format!("Unknown profile '{name}'")))?;
+            chain.push(name);
+            next = profile.get("inherits").map(String::as_str);
+        }
+
+        let mut resolved = Profile::new();
+        for name in chain.iter().rev() {
+            resolved.extend(self.profiles[*name].clone());
+        }
+        resolved.remove("inherits");
+        Ok(resolved)
+    }
+
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+}
+
+fn parse_value(raw: &str) -> Option<String> {
+    for quote in ['"', '\''] {
+        if let Some(rest) = raw.strip_prefix(quote) {
+            let (value, tail) = rest.split_once(quote)?;
+            let tail = tail.trim();
+            return (tail.is_empty() || tail.starts_with('#')).then(|| value.to_string());
+        }
+    }
+    let value = raw.split_once('#').map_or(raw, |(value, _)| value).trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> ProfileFile {
+        ProfileFile::parse(content).unwrap()
+    }
+
+    fn error(content: &str) -> String {
+        ProfileFile::parse(content).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn values_comments_and_quotes() {
+        let file = parse(
+            "# shared settings\n\
+             [profile.default]\n\
+             timeout = 30s # trailing comment\n\
+             user-agent = \"probe # 1\"\n\
+             targets = 'db:5432, cache:6379'\n",
+        );
+        let profile = file.profile("default").unwrap();
+        assert_eq!(profile["timeout"], "30s");
+        assert_eq!(profile["user-agent"], "probe # 1");
+        assert_eq!(profile["targets"], "db:5432, cache:6379");
+    }
+
+    #[test]
+    fn inheritance_overrides_parent_values() {
+        let file = parse(
+            "[profile.default]\ntimeout = 30s\ninterval = 1s\n\
+             [profile.ci]\ninherits = default\ntimeout = 2m\n",
+        );
+        let ci = file.profile("ci").unwrap();
+        assert_eq!(ci["timeout"], "2m");
+        assert_eq!(ci["interval"], "1s");
+        assert!(!ci.contains_key("inherits"));
+    }
+
+    #[test]
+    fn inheritance_cycle_is_rejected() {
+        let file = parse("[profile.a]\ninherits = b\n[profile.b]\ninherits = a\n");
+        assert!(file.profile("a").unwrap_err().to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn unknown_profile() {
+        let file = parse("[profile.default]\n");
+        assert!(file.has_profile("default"));
+        assert!(file.profile("prod").is_err());
+    }
+
+    #[test]
+    fn malformed_lines() {
+        assert!(error("timeout = 1s\n").contains("line 1: key outside"));
+        assert!(error("[profile.a]\ntimeout\n").contains("line 2: expected key = value"));
+        assert!(error("[server]\n").contains("expected [profile.<name>]"));
+        assert!(error("[profile.]\n").contains("expected [profile.<name>]"));
+        assert!(error("[profile.a]\ntimeout = \"1s\" x\n").contains("invalid value"));
+        assert!(error("[profile.a]\ntimeout = \"1s\n").contains("invalid value"));
+        assert!(error("[profile.a]\ntimeout =\n").contains("invalid value"));
+    }
+
+    #[test]
+    fn later_layer_wins_on_merge() {
+        let lower = PartialWaitConfig {
+            timeout: Some("30s".to_string()),
+            interval: Some("1s".to_string()),
+            ..PartialWaitConfig::default()
+        };
+        let upper = PartialWaitConfig {
+            timeout: Some("2m".to_string()),
+            ..PartialWaitConfig::default()
+        };
+        let merged = lower.merge(upper);
+        assert_eq!(merged.timeout.as_deref(), Some("2m"));
+        assert_eq!(merged.interval.as_deref(), Some("1s"));
+        assert_eq!(merged.user_agent, None);
+    }
+
+    #[test]
+    fn presets_only_seed_timing() {
+        let layer = Preset::Ci.layer();
+        assert_eq!(layer.timeout.as_deref(), Some("2m"));
+        assert_eq!(layer.targets, None);
+    }
+}
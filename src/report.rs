@@ -0,0 +1,536 @@
+use std::time::Duration;
+
+pub use crate::json::{Number, Value};
+
+use crate::plan::PhaseResult;
+use crate::types::{
+    CancelReason, Error, Tags, TargetError, TargetResult, TargetResultSliceExt, WaitOutcome,
+    WaitResult,
+};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/grok-rs/waitup/schema/report-v1.json",
+  "title": "waitup report",
+  "type": "object",
+  "required": [
+    "schema_version", "success", "outcome", "elapsed_ms", "phases", "error", "code"
+  ],
+  "properties": {
+    "schema_version": { "const": 1 },
+    "success": { "type": "boolean" },
+    "outcome": { "$ref": "#/$defs/outcome" },
+    "elapsed_ms": { "type": "integer", "minimum": 0 },
+    "error": { "type": ["string", "null"] },
+    "code": { "$ref": "#/$defs/code" },
+    "phases": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["name", "success", "outcome", "elapsed_ms", "summary", "targets"],
+        "properties": {
+          "name": { "type": "string" },
+          "success": { "type": "boolean" },
+          "outcome": { "$ref": "#/$defs/outcome" },
+          "elapsed_ms": { "type": "integer", "minimum": 0 },
+          "summary": {
+            "allOf": [{ "$ref": "#/$defs/counts" }],
+            "required": ["ready", "failed", "cancelled", "by_kind", "by_tag"],
+            "properties": {
+              "ready": true,
+              "failed": true,
+              "cancelled": true,
+              "by_kind": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/counts" }
+              },
+              "by_tag": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/counts" }
+              }
+            }
+          },
+          "targets": {
+            "type": "array",
+            "items": {
+              "type": "object",
+              "required": [
+                "target", "tags", "success", "outcome", "elapsed_ms", "attempts",
+                "successes", "error", "code", "cancel_reason"
+              ],
+              "properties": {
+                "target": { "type": "string" },
+                "tags": {
+                  "type": "object",
+                  "additionalProperties": { "type": "string" }
+                },
+                "success": { "type": "boolean" },
+                "outcome": { "$ref": "#/$defs/outcome" },
+                "elapsed_ms": { "type": "integer", "minimum": 0 },
+                "attempts": { "type": "integer", "minimum": 0 },
+                "successes": { "type": "integer", "minimum": 0 },
+                "error": { "type": ["string", "null"] },
+                "code": { "$ref": "#/$defs/code" },
+                "cancel_reason": {
+                  "enum": [null, "any_ready", "dependency_failed", "deadline"]
+                }
+              }
+            }
+          }
+        }
+      }
+    }
+  },
+  "$defs": {
+    "outcome": {
+      "enum": ["ready", "timed_out", "cancelled", "retries_exhausted", "fatal_error"]
+    },
+    "counts": {
+      "type": "object",
+      "required": ["ready", "failed", "cancelled"],
+      "properties": {
+        "ready": { "type": "integer", "minimum": 0 },
+        "failed": { "type": "integer", "minimum": 0 },
+        "cancelled": { "type": "integer", "minimum": 0 }
+      }
+    },
+    "code": {
+      "enum": [
+        null,
+        "CONFIG_ERROR",
+        "CONNECTION_FAILED",
+        "CONNECTION_REFUSED",
+        "DNS_FAILURE",
+        "TLS_ERROR",
+        "STATUS_MISMATCH",
+        "RESPONSE_MISMATCH",
+        "CERTIFICATE_ERROR",
+        "TIMEOUT",
+        "RETRIES_EXHAUSTED",
+        "COMMAND_FAILED",
+        "CANCELLED"
+      ]
+    }
+  }
+}"##;
+
+#[derive(Debug, Clone)]
+pub struct TargetReport {
+    pub target: String,
+    pub tags: Tags,
+    pub outcome: WaitOutcome,
+    pub elapsed: Duration,
+    pub attempts: u32,
+    pub successes: u32,
+    pub error: Option<String>,
+    pub code: Option<&'static str>,
+    pub cancel_reason: Option<CancelReason>,
+}
+
+// Targets stopped or skipped once the wait was decided count as cancelled,
+// not failed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub ready: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub counts: Counts,
+    pub by_kind: Vec<(String, Counts)>,
+    pub by_tag: Vec<(String, Counts)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhaseReport {
+    pub name: String,
+    pub outcome: WaitOutcome,
+    pub elapsed: Duration,
+    pub summary: Summary,
+    pub targets: Vec<TargetReport>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub outcome: WaitOutcome,
+    pub elapsed: Duration,
+    pub phases: Vec<PhaseReport>,
+    pub error: Option<String>,
+    pub code: Option<&'static str>,
+}
+
+fn millis(duration: Duration) -> Value {
+    Value::Number(Number::from(
+        u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+    ))
+}
+
+fn optional(value: Option<&str>) -> Value {
+    value.map_or(Value::Null, |s| Value::String(s.to_string()))
+}
+
+impl Counts {
+    fn of(results: &[&TargetResult]) -> Self {
+        let mut counts = Self::default();
+        for result in results {
+            match result.outcome() {
+                WaitOutcome::Ready => counts.ready += 1,
+                WaitOutcome::Cancelled => counts.cancelled += 1,
+                _ => counts.failed += 1,
+            }
+        }
+        counts
+    }
+
+    fn fields(self) -> Vec<(String, Value)> {
+        vec![
+            ("ready".into(), Value::Number(self.ready.into())),
+            ("failed".into(), Value::Number(self.failed.into())),
+            ("cancelled".into(), Value::Number(self.cancelled.into())),
+        ]
+    }
+}
+
+impl Summary {
+    fn of(results: &[TargetResult]) -> Self {
+        let (ready, unready) = results.partition_by_success();
+        let unready = Counts::of(&unready);
+        let by_kind = results
+            .group_by_kind()
+            .into_iter()
+            .map(|(kind, group)| (kind.to_string(), Counts::of(&group)))
+            .collect();
+
+        let mut keys: Vec<&str> = results
+            .iter()
+            .flat_map(|r| r.target.tags.iter().map(|(k, _)| k.as_str()))
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let by_tag = keys
+            .into_iter()
+            .flat_map(|key| {
+                results
+                    .group_by_tag(key)
+                    .into_iter()
+                    .map(move |(value, group)| (format!("{key}={value}"), Counts::of(&group)))
+            })
+            .collect();
+
+        Self {
+            counts: Counts {
+                ready: ready.len(),
+                ..unready
+            },
+            by_kind,
+            by_tag,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let groups = |groups: &[(String, Counts)]| {
+            Value::Object(
+                groups
+                    .iter()
+                    .map(|(name, counts)| (name.clone(), Value::Object(counts.fields())))
+                    .collect(),
+            )
+        };
+        let mut fields = self.counts.fields();
+        fields.push(("by_kind".into(), groups(&self.by_kind)));
+        fields.push(("by_tag".into(), groups(&self.by_tag)));
+        Value::Object(fields)
+    }
+}
+
+impl TargetReport {
+    fn to_json(&self) -> Value {
+        let tags = self
+            .tags
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+            .collect();
+        Value::Object(vec![
+            ("target".into(), Value::String(self.target.clone())),
+            ("tags".into(), Value::Object(tags)),
+            (
+                "success".into(),
+                Value::Bool(self.outcome == WaitOutcome::Ready),
+            ),
+            (
+                "outcome".into(),
+                Value::String(self.outcome.as_str().into()),
+            ),
+            ("elapsed_ms".into(), millis(self.elapsed)),
+            ("attempts".into(), Value::Number(self.attempts.into())),
+            ("successes".into(), Value::Number(self.successes.into())),
+            ("error".into(), optional(self.error.as_deref())),
+            ("code".into(), optional(self.code)),
+            (
+                "cancel_reason".into(),
+                optional(self.cancel_reason.map(CancelReason::as_str)),
+            ),
+        ])
+    }
+}
+
+impl PhaseReport {
+    fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("name".into(), Value::String(self.name.clone())),
+            (
+                "success".into(),
+                Value::Bool(self.outcome == WaitOutcome::Ready),
+            ),
+            (
+                "outcome".into(),
+                Value::String(self.outcome.as_str().into()),
+            ),
+            ("elapsed_ms".into(), millis(self.elapsed)),
+            ("summary".into(), self.summary.to_json()),
+            (
+                "targets".into(),
+                Value::Array(self.targets.iter().map(TargetReport::to_json).collect()),
+            ),
+        ])
+    }
+}
+
+impl Report {
+    pub fn from_phases(results: &[PhaseResult]) -> Self {
+        let phases: Vec<PhaseReport> = results
+            .iter()
+            .map(|phase| phase.result.to_phase_report(&phase.name))
+            .collect();
+        let error = results.iter().find_map(|phase| {
+            let e = phase.result.error.as_ref()?;
+            Some(if results.len() == 1 {
+                e.clone()
+            } else {
+                Error::Phase {
+                    name: phase.name.clone(),
+                    source: Box::new(e.clone()),
+                }
+            })
+        });
+        Self {
+            outcome: WaitOutcome::of(error.as_ref()),
+            elapsed: phases.iter().map(|p| p.elapsed).sum(),
+            phases,
+            error: error.as_ref().map(ToString::to_string),
+            code: error.as_ref().map(Error::code),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Object(vec![
+            (
+                "schema_version".into(),
+                Value::Number(SCHEMA_VERSION.into()),
+            ),
+            (
+                "success".into(),
+                Value::Bool(self.outcome == WaitOutcome::Ready),
+            ),
+            (
+                "outcome".into(),
+                Value::String(self.outcome.as_str().into()),
+            ),
+            ("elapsed_ms".into(), millis(self.elapsed)),
+            (
+                "phases".into(),
+                Value::Array(self.phases.iter().map(PhaseReport::to_json).collect()),
+            ),
+            ("error".into(), optional(self.error.as_deref())),
+            ("code".into(), optional(self.code)),
+        ])
+    }
+}
+
+impl From<&TargetResult> for TargetReport {
+    fn from(result: &TargetResult) -> Self {
+        Self {
+            target: result.target.to_string(),
+            tags: result.target.tags.clone(),
+            outcome: result.outcome(),
+            elapsed: result.elapsed,
+            attempts: result.attempts,
+            successes: result.successes,
+            error: result.error.as_ref().map(ToString::to_string),
+            code: result.error.as_ref().map(TargetError::code),
+            cancel_reason: result.error.as_ref().and_then(TargetError::cancel_reason),
+        }
+    }
+}
+
+impl WaitResult {
+    pub fn to_phase_report(&self, name: &str) -> PhaseReport {
+        PhaseReport {
+            name: name.to_string(),
+            outcome: self.outcome(),
+            elapsed: self.elapsed,
+            summary: Summary::of(&self.targets),
+            targets: self.targets.iter().map(TargetReport::from).collect(),
+        }
+    }
+
+    pub fn to_report(&self) -> Report {
+        Report {
+            outcome: self.outcome(),
+            elapsed: self.elapsed,
+            phases: vec![self.to_phase_report("targets")],
+            error: self.error.as_ref().map(ToString::to_string),
+            code: self.error.as_ref().map(Error::code),
+        }
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        self.to_report().to_json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::types::{Target, TargetFailure, TargetOptions};
+
+    fn target(spec: &str) -> Arc<Target> {
+        Arc::new(Target::parse(spec, &TargetOptions::default()).unwrap())
+    }
+
+    fn sample() -> WaitResult {
+        let failure = TargetFailure {
+            target: "cache:6379".to_string(),
+            attempts: 3,
+            elapsed: Duration::from_millis(2500),
+            last_error: Some(Box::new(Error::Connection("refused \"now\"".to_string()))),
+            pending: None,
+        };
+        let error = Error::Timeout {
+            targets: vec![failure],
+        };
+        WaitResult {
+            targets: vec![
+                TargetResult {
+                    target: target("db:5432#tier=core"),
+                    elapsed: Duration::from_micros(1500),
+                    attempts: 1,
+                    successes: 1,
+                    error: None,
+                },
+                TargetResult {
+                    target: target("cache:6379#tier=edge"),
+                    elapsed: Duration::from_millis(2500),
+                    attempts: 3,
+                    successes: 0,
+                    error: Some(error.clone().into()),
+                },
+                TargetResult {
+                    target: target("queue:5672#tier=edge"),
+                    elapsed: Duration::from_millis(2400),
+                    attempts: 2,
+                    successes: 0,
+                    error: Some(TargetError::Stopped(CancelReason::DependencyFailed)),
+                },
+            ],
+            elapsed: Duration::from_millis(2501),
+            error: Some(error),
+        }
+    }
+
+    fn required<'a>(schema: &'a Value, path: &[&str]) -> Vec<&'a str> {
+        let node = path.iter().fold(schema, |node, key| node.get(key).unwrap());
+        let Some(Value::Array(keys)) = node.get("required") else {
+            panic!("no required list at {path:?}");
+        };
+        keys.iter()
+            .map(|key| match key {
+                Value::String(key) => key.as_str(),
+                other => panic!("unexpected {other}"),
+            })
+            .collect()
+    }
+
+    fn assert_has(object: &Value, keys: &[&str]) {
+        for key in keys {
+            assert!(object.get(key).is_some(), "missing '{key}' in {object}");
+        }
+    }
+
+    #[test]
+    fn report_has_every_required_field() {
+        let schema = Value::parse(SCHEMA).expect("schema is valid JSON");
+        let report = Value::parse(&sample().to_json_value().to_string()).unwrap();
+
+        assert_has(&report, &required(&schema, &[]));
+        let Some(Value::Array(phases)) = report.get("phases") else {
+            panic!("phases is not an array");
+        };
+        let phase_path = ["properties", "phases", "items"];
+        let summary_path = [&phase_path[..], &["properties", "summary"]].concat();
+        let target_path = [&phase_path[..], &["properties", "targets", "items"]].concat();
+        for phase in phases {
+            assert_has(phase, &required(&schema, &phase_path));
+            assert_has(
+                phase.get("summary").unwrap(),
+                &required(&schema, &summary_path),
+            );
+            let Some(Value::Array(targets)) = phase.get("targets") else {
+                panic!("targets is not an array");
+            };
+            for target in targets {
+                assert_has(target, &required(&schema, &target_path));
+            }
+        }
+    }
+
+    #[test]
+    fn report_serialization() {
+        assert_eq!(
+            sample().to_json_value().to_string(),
+            concat!(
+                r#"{"schema_version":1,"success":false,"outcome":"timed_out","elapsed_ms":2501,"#,
+                r#""phases":[{"name":"targets","success":false,"outcome":"timed_out","elapsed_ms":2501,"#,
+                r#""summary":{"ready":1,"failed":1,"cancelled":1,"#,
+                r#""by_kind":{"tcp":{"ready":1,"failed":1,"cancelled":1}},"#,
+                r#""by_tag":{"tier=core":{"ready":1,"failed":0,"cancelled":0},"#,
+                r#""tier=edge":{"ready":0,"failed":1,"cancelled":1}}},"#,
+                r#""targets":[{"target":"db:5432","tags":{"tier":"core"},"success":true,"#,
+                r#""outcome":"ready","elapsed_ms":1,"attempts":1,"successes":1,"error":null,"#,
+                r#""code":null,"cancel_reason":null},{"target":"cache:6379","tags":{"tier":"edge"},"#,
+                r#""success":false,"outcome":"timed_out","elapsed_ms":2500,"attempts":3,"successes":0,"#,
+                r#""error":"Timeout waiting for cache:6379 (3 attempts in 2s 500ms, last error: refused \"now\")","#,
+                r#""code":"TIMEOUT","cancel_reason":null},{"target":"queue:5672","tags":{"tier":"edge"},"#,
+                r#""success":false,"outcome":"cancelled","elapsed_ms":2400,"attempts":2,"successes":0,"#,
+                r#""error":"stopped: an earlier target failed","code":"CANCELLED","#,
+                r#""cancel_reason":"dependency_failed"}]}],"#,
+                r#""error":"Timeout waiting for cache:6379 (3 attempts in 2s 500ms, last error: refused \"now\")","#,
+                r#""code":"TIMEOUT"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn single_phase_matches_wait_result() {
+        let phase = PhaseResult {
+            name: "targets".to_string(),
+            result: sample(),
+        };
+        assert_eq!(
+            Report::from_phases(&[phase]).to_json(),
+            sample().to_json_value()
+        );
+    }
+
+    #[test]
+    fn elapsed_is_whole_milliseconds() {
+        assert_eq!(millis(Duration::from_micros(1999)).to_string(), "1");
+        assert_eq!(millis(Duration::MAX).to_string(), u64::MAX.to_string());
+    }
+}
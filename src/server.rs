@@ -0,0 +1,200 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::connection::{Watch, connect_error};
+use crate::json::Value;
+use crate::types::{Result, Target, WaitConfig};
+
+const MAX_REQUEST_HEAD: usize = 8192;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Accept errors such as running out of file descriptors tend to persist.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+struct Readiness {
+    watch: Watch,
+    // Set once the wait is over: whether it succeeded.
+    outcome: OnceLock<bool>,
+}
+
+impl Readiness {
+    fn is_ready(&self) -> bool {
+        self.outcome.get().copied().unwrap_or(false)
+    }
+
+    fn body(&self) -> Value {
+        let targets = self
+            .watch
+            .status()
+            .into_iter()
+            .map(|status| {
+                Value::Object(vec![
+                    ("target".into(), Value::String(status.target.to_string())),
+                    ("state".into(), Value::String(status.state.as_str().into())),
+                    ("attempts".into(), Value::Number(status.attempts.into())),
+                ])
+            })
+            .collect();
+        Value::Object(vec![
+            ("ready".into(), Value::Bool(self.is_ready())),
+            ("targets".into(), Value::Array(targets)),
+        ])
+    }
+}
+
+/// Waits for `targets` and serves their state over HTTP on `addr`.
+///
+/// `GET /healthz` answers 200 while the server runs. `GET /readyz` answers
+/// 200 once the wait has succeeded and 503 before that or if it failed, with
+/// each target's state as JSON. Only returns if `addr` cannot be bound.
+pub async fn serve_readiness<I>(targets: I, config: WaitConfig, addr: SocketAddr) -> Result<()>
+where
+    I: IntoIterator,
+    I::Item: Into<Arc<Target>>,
+{
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| connect_error(&e))?;
+    serve(listener, targets, config).await
+}
+
+async fn serve<I>(listener: TcpListener, targets: I, config: WaitConfig) -> Result<()>
+where
+    I: IntoIterator,
+    I::Item: Into<Arc<Target>>,
+{
+    let readiness = Arc::new(Readiness {
+        watch: Watch::new(targets),
+        outcome: OnceLock::new(),
+    });
+    let config = Arc::new(config);
+    tokio::spawn({
+        let readiness = Arc::clone(&readiness);
+        async move {
+            let result = readiness.watch.run(&config).await;
+            let _ = readiness.outcome.set(result.is_success());
+        }
+    });
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::debug!(error = %e, "accepting a readiness request failed");
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let readiness = Arc::clone(&readiness);
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &readiness).await {
+                tracing::debug!(%peer, error = %e, "readiness request failed");
+            }
+        });
+    }
+}
+
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(head)
+}
+
+fn route(head: &[u8], readiness: &Readiness) -> (&'static str, String) {
+    let head = String::from_utf8_lossy(head);
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let (method, path) = (request_line.next(), request_line.next());
+    let path = path.map(|p| p.split('?').next().unwrap_or(p));
+    match (method, path) {
+        (Some("GET" | "HEAD"), Some("/healthz")) => ("200 OK", "{\"status\":\"ok\"}".to_string()),
+        (Some("GET" | "HEAD"), Some("/readyz")) => {
+            let status = if readiness.is_ready() {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            (status, readiness.body().to_string())
+        }
+        (Some("GET" | "HEAD"), Some(_)) => ("404 Not Found", "{\"error\":\"not found\"}".into()),
+        _ => (
+            "405 Method Not Allowed",
+            "{\"error\":\"method not allowed\"}".into(),
+        ),
+    }
+}
+
+async fn respond(mut stream: TcpStream, readiness: &Readiness) -> std::io::Result<()> {
+    let head = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    let (status, body) = route(&head, readiness);
+    let body = if head.starts_with(b"HEAD ") {
+        ""
+    } else {
+        &body
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TargetOptions;
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nHost: test\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn readyz_follows_the_wait() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = port.local_addr().unwrap().to_string();
+        drop(port);
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let targets = [Target::parse(&target, &TargetOptions::default()).unwrap()];
+        let config = WaitConfig {
+            initial_interval: Duration::from_millis(50),
+            ..WaitConfig::default()
+        };
+        tokio::spawn(serve(server, targets, config));
+        // Let the first probe start.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(get(addr, "/healthz").await.starts_with("HTTP/1.1 200 OK"));
+        let pending = get(addr, "/readyz").await;
+        assert!(pending.starts_with("HTTP/1.1 503"), "{pending}");
+        assert!(pending.contains("\"state\":\"probing\""), "{pending}");
+        assert!(get(addr, "/other").await.starts_with("HTTP/1.1 404"));
+
+        let _listener = std::net::TcpListener::bind(&target).unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let ready = get(addr, "/readyz").await;
+        assert!(ready.starts_with("HTTP/1.1 200 OK"), "{ready}");
+        let body = ready.split("\r\n\r\n").nth(1).unwrap();
+        assert!(body.starts_with(&format!(
+            "{{\"ready\":true,\"targets\":[{{\"target\":\"{target}\",\"state\":\"ready\","
+        )));
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use rustls_platform_verifier::BuilderVerifierExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::rustls::crypto::aws_lc_rs;
+use tokio_rustls::rustls::pki_types::ServerName;
+
+use crate::connection::tcp_connect;
+use crate::dns::resolve;
+use crate::types::{Error, Hostname, Result, StartTls, TcpOptions, TlsOptions, WaitConfig};
+
+const POSTGRES_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+
+const SEQUENCE: u8 = 0x30;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const CONTEXT_0: u8 = 0xa0;
+
+pub fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+        (len, &rest[count..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+fn parse_time(tag: u8, raw: &[u8]) -> Option<SystemTime> {
+    let raw = core::str::from_utf8(raw).ok()?.strip_suffix('Z')?;
+    let full = match tag {
+        UTC_TIME => {
+            let year: u32 = raw.get(..2)?.parse().ok()?;
+            let century = if year >= 50 { "19" } else { "20" };
+            format!("{century}{raw}")
+        }
+        GENERALIZED_TIME => raw.to_string(),
+        _ => return None,
+    };
+    let digits = full.get(..14)?;
+    let rfc3339 = format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14]
+    );
+    humantime::parse_rfc3339(&rfc3339).ok()
+}
+
+fn certificate_not_after(der: &[u8]) -> Option<SystemTime> {
+    let (SEQUENCE, cert, _) = read_tlv(der)? else {
+        return None;
+    };
+    let (SEQUENCE, mut tbs, _) = read_tlv(cert)? else {
+        return None;
+    };
+
+    if tbs.first() == Some(&CONTEXT_0) {
+        tbs = read_tlv(tbs)?.2;
+    }
+    for _ in 0..3 {
+        tbs = read_tlv(tbs)?.2;
+    }
+
+    let (SEQUENCE, validity, _) = read_tlv(tbs)? else {
+        return None;
+    };
+    let (_, _, validity) = read_tlv(validity)?;
+    let (tag, not_after, _) = read_tlv(validity)?;
+    parse_time(tag, not_after)
+}
+
+pub fn check_expiry(cert: Option<&[u8]>, min_validity: Duration) -> Result<()> {
+    let not_after = cert
+        .and_then(certificate_not_after)
+        .ok_or_else(|| Error::Certificate("could not read peer certificate".to_string()))?;
+
+    let remaining = not_after
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    if remaining < min_validity {
+        return Err(Error::Certificate(format!(
+            "certificate expires at {}, less than {} days away",
+            humantime::format_rfc3339_seconds(not_after),
+            min_validity.as_secs() / 86_400
+        )));
+    }
+    Ok(())
+}
+
+fn client_config(alpn: Option<&str>) -> Result<ClientConfig> {
+    let mut config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(BuilderVerifierExt::with_platform_verifier)
+        .map_err(|e| Error::Connection(format!("TLS configuration error: {e}")))?
+        .with_no_client_auth();
+    if let Some(alpn) = alpn {
+        config.alpn_protocols = vec![alpn.as_bytes().to_vec()];
+    }
+    Ok(config)
+}
+
+pub async fn tls_connect(
+    host: &Hostname,
+    port: u16,
+    options: &TlsOptions,
+    config: &WaitConfig,
+) -> Result<TlsStream<TcpStream>> {
+    let host_str = host.host_str();
+    let name = options.server_name.as_deref().unwrap_or(&host_str);
+    let server_name = ServerName::try_from(name.to_string())
+        .map_err(|e| Error::Config(format!("Invalid TLS server name '{name}': {e}")))?;
+    let connector = TlsConnector::from(Arc::new(client_config(options.alpn.as_deref())?));
+
+    let addrs = resolve(host, port, &config.dns_servers).await?;
+    let mut stream = tcp_connect(&addrs, TcpOptions::default(), config).await?;
+    if let Some(proto) = options.starttls {
+        negotiate_starttls(&mut stream, proto)
+            .await
+            .map_err(|e| match e {
+                Error::Connection(msg) => {
+                    Error::Connection(format!("{} STARTTLS failed: {msg}", proto.as_str()))
+                }
+                other => other,
+            })?;
+    }
+    connector
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| Error::Tls(format!("TLS handshake with {host}:{port} failed: {e}")))
+}
+
+pub async fn tls_handshake(
+    host: &Hostname,
+    port: u16,
+    options: &TlsOptions,
+    config: &WaitConfig,
+) -> Result<()> {
+    let stream = tls_connect(host, port, options, config).await?;
+    let (_, session) = stream.get_ref();
+
+    if let Some(alpn) = &options.alpn
+        && session.alpn_protocol() != Some(alpn.as_bytes())
+    {
+        return Err(Error::Tls(format!(
+            "Expected ALPN protocol '{alpn}', negotiated {:?}",
+            session
+                .alpn_protocol()
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default()
+        )));
+    }
+
+    if let Some(min_validity) = options.cert_min_validity {
+        let cert = session.peer_certificates().and_then(|c| c.first());
+        check_expiry(cert.map(|c| c.as_ref()), min_validity)?;
+    }
+
+    Ok(())
+}
+
+async fn read_reply(reader: &mut BufReader<&mut TcpStream>) -> Result<String> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| Error::Connection(e.to_string()))?;
+        if read == 0 {
+            return Err(Error::Connection("connection closed".to_string()));
+        }
+        reply.push_str(&line);
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(reply);
+        }
+    }
+}
+
+async fn expect_reply(reader: &mut BufReader<&mut TcpStream>, prefix: &str) -> Result<()> {
+    let reply = read_reply(reader).await?;
+    if reply.starts_with(prefix) {
+        Ok(())
+    } else {
+        Err(Error::Connection(format!(
+            "unexpected reply {:?}",
+            reply.trim_end()
+        )))
+    }
+}
+
+async fn send(reader: &mut BufReader<&mut TcpStream>, command: &str) -> Result<()> {
+    reader
+        .get_mut()
+        .write_all(command.as_bytes())
+        .await
+        .map_err(|e| Error::Connection(e.to_string()))
+}
+
+async fn negotiate_starttls(stream: &mut TcpStream, proto: StartTls) -> Result<()> {
+    match proto {
+        StartTls::Smtp => {
+            let mut reader = BufReader::new(stream);
+            expect_reply(&mut reader, "220").await?;
+            send(&mut reader, "EHLO waitup\r\n").await?;
+            expect_reply(&mut reader, "250").await?;
+            send(&mut reader, "STARTTLS\r\n").await?;
+            expect_reply(&mut reader, "220").await
+        }
+        StartTls::Imap => {
+            let mut reader = BufReader::new(stream);
+            expect_reply(&mut reader, "* OK").await?;
+            send(&mut reader, "a1 STARTTLS\r\n").await?;
+            loop {
+                let reply = read_reply(&mut reader).await?;
+                if reply.starts_with("a1 OK") {
+                    return Ok(());
+                }
+                if reply.starts_with("a1 ") {
+                    return Err(Error::Connection(format!(
+                        "unexpected reply {:?}",
+                        reply.trim_end()
+                    )));
+                }
+            }
+        }
+        StartTls::Postgres => {
+            stream
+                .write_all(&POSTGRES_SSL_REQUEST)
+                .await
+                .map_err(|e| Error::Connection(e.to_string()))?;
+            let answer = stream
+                .read_u8()
+                .await
+                .map_err(|e| Error::Connection(e.to_string()))?;
+            match answer {
+                b'S' => Ok(()),
+                _ => Err(Error::Connection("server does not accept SSL".to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let content = parts.concat();
+        let mut out = vec![tag];
+        match u8::try_from(content.len()) {
+            Ok(len) if len < 0x80 => out.push(len),
+            _ => {
+                let len = u16::try_from(content.len()).unwrap();
+                out.push(0x82);
+                out.extend_from_slice(&len.to_be_bytes());
+            }
+        }
+        out.extend_from_slice(&content);
+        out
+    }
+
+    // Enough of an X.509 certificate to reach the validity field; the
+    // subject is padded so the outer lengths take the long form.
+    fn certificate(not_after: &[u8], versioned: bool) -> Vec<u8> {
+        let version = der(CONTEXT_0, &[&der(0x02, &[&[2]])]);
+        let validity = der(SEQUENCE, &[&der(UTC_TIME, &[b"200101000000Z"]), not_after]);
+        let subject = der(SEQUENCE, &[&[b'x'; 200]]);
+        let mut tbs: Vec<&[u8]> = Vec::new();
+        if versioned {
+            tbs.push(&version);
+        }
+        let serial = der(0x02, &[&[0x01, 0x23]]);
+        let algorithm = der(SEQUENCE, &[]);
+        let issuer = der(SEQUENCE, &[]);
+        tbs.extend([&serial[..], &algorithm, &issuer, &validity, &subject]);
+        der(SEQUENCE, &[&der(SEQUENCE, &tbs), &algorithm])
+    }
+
+    fn at(rfc3339: &str) -> SystemTime {
+        humantime::parse_rfc3339(rfc3339).unwrap()
+    }
+
+    #[test]
+    fn reads_not_after_from_certificates() {
+        let utc = der(UTC_TIME, &[b"300615123000Z"]);
+        let expected = Some(at("2030-06-15T12:30:00Z"));
+        assert_eq!(certificate_not_after(&certificate(&utc, true)), expected);
+        assert_eq!(certificate_not_after(&certificate(&utc, false)), expected);
+
+        let generalized = der(GENERALIZED_TIME, &[b"20991231235959Z"]);
+        assert_eq!(
+            certificate_not_after(&certificate(&generalized, true)),
+            Some(at("2099-12-31T23:59:59Z"))
+        );
+
+        let cert = certificate(&utc, true);
+        assert_eq!(certificate_not_after(&cert[..cert.len() - 1]), None);
+        assert_eq!(certificate_not_after(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn utc_time_years_pivot_at_1950() {
+        assert_eq!(
+            parse_time(UTC_TIME, b"491231235959Z"),
+            Some(at("2049-12-31T23:59:59Z"))
+        );
+        assert_eq!(
+            parse_time(UTC_TIME, b"991231235959Z"),
+            Some(at("1999-12-31T23:59:59Z"))
+        );
+        assert_eq!(parse_time(UTC_TIME, b"500101000000"), None);
+        assert_eq!(parse_time(0x04, b"20500101000000Z"), None);
+    }
+
+    #[test]
+    fn expiry_is_checked_against_min_validity() {
+        let expired = certificate(&der(UTC_TIME, &[b"200102000000Z"]), true);
+        let err = check_expiry(Some(&expired), Duration::from_secs(86_400)).unwrap_err();
+        assert!(err.to_string().contains("2020-01-02T00:00:00Z"), "{err}");
+
+        let far = certificate(&der(GENERALIZED_TIME, &[b"20991231235959Z"]), true);
+        check_expiry(Some(&far), Duration::from_secs(30 * 86_400)).unwrap();
+        assert!(matches!(
+            check_expiry(None, Duration::ZERO),
+            Err(Error::Certificate(_))
+        ));
+    }
+}
@@ -1,69 +1,1319 @@
 use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::num::NonZeroU16;
+use core::str::FromStr;
 use core::time::Duration;
-use reqwest::Url;
+use percent_encoding::percent_decode_str;
+use reqwest::{StatusCode, Url};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Semaphore, watch};
+use tokio::time::Instant;
 
-#[derive(thiserror::Error, Debug)]
+use crate::checker::{self, Checker};
+use crate::command::{join_words, split_words};
+use crate::human;
+
+#[derive(thiserror::Error, Debug, Clone)]
 pub enum Error {
     #[error("{0}")]
     Config(String),
     #[error("{0}")]
     Connection(String),
-    #[error("Timeout waiting for {0}")]
-    Timeout(String),
+    #[error("{0}")]
+    Refused(String),
+    #[error("DNS lookup failed for {0}")]
+    Dns(String),
+    #[error("{0}")]
+    Tls(String),
+    #[error("Expected {expected} status, got {actual}")]
+    Status {
+        expected: String,
+        actual: StatusCode,
+    },
+    #[error("{0}")]
+    Response(String),
+    #[error("Certificate check failed: {0}")]
+    Certificate(String),
+    #[error("Timeout waiting for {}", join_failures(.targets))]
+    Timeout { targets: Vec<TargetFailure> },
+    #[error("Gave up on {0}")]
+    Exhausted(TargetFailure),
+    #[error("Phase '{name}' failed: {source}")]
+    Phase { name: String, source: Box<Error> },
     #[error("Command failed: {0}")]
     Command(String),
 }
 
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Config(_) => "CONFIG_ERROR",
+            Self::Connection(_) => "CONNECTION_FAILED",
+            Self::Refused(_) => "CONNECTION_REFUSED",
+            Self::Dns(_) => "DNS_FAILURE",
+            Self::Tls(_) => "TLS_ERROR",
+            Self::Status { .. } => "STATUS_MISMATCH",
+            Self::Response(_) => "RESPONSE_MISMATCH",
+            Self::Certificate(_) => "CERTIFICATE_ERROR",
+            Self::Timeout { .. } => "TIMEOUT",
+            Self::Exhausted(_) => "RETRIES_EXHAUSTED",
+            Self::Phase { source, .. } => source.code(),
+            Self::Command(_) => "COMMAND_FAILED",
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(_)
+                | Self::Refused(_)
+                | Self::Dns(_)
+                | Self::Tls(_)
+                | Self::Status { .. }
+                | Self::Response(_)
+        )
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            Self::Config(_) | Self::Certificate(_) | Self::Command(_) => true,
+            Self::Phase { source, .. } => source.is_fatal(),
+            _ => false,
+        }
+    }
+
+    pub fn is_config(&self) -> bool {
+        match self {
+            Self::Config(_) => true,
+            Self::Phase { source, .. } => source.is_config(),
+            _ => false,
+        }
+    }
+}
+
+pub const ERROR_CODES: &[&str] = &[
+    "CONFIG_ERROR",
+    "CONNECTION_FAILED",
+    "CONNECTION_REFUSED",
+    "DNS_FAILURE",
+    "TLS_ERROR",
+    "STATUS_MISMATCH",
+    "RESPONSE_MISMATCH",
+    "CERTIFICATE_ERROR",
+    "TIMEOUT",
+    "RETRIES_EXHAUSTED",
+    "COMMAND_FAILED",
+];
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+// Why a target that answers is not counted as ready yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pending {
+    Successes { have: u32, need: u32 },
+    Stability { up_for: Duration, need: Duration },
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Successes { have, need } => write!(f, "{have}/{need} consecutive successes"),
+            Self::Stability { up_for, need } => write!(
+                f,
+                "not stable for {} / {}",
+                human::duration(*up_for),
+                human::duration(*need)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetFailure {
+    pub target: String,
+    pub attempts: u32,
+    pub elapsed: Duration,
+    pub last_error: Option<Box<Error>>,
+    // Set when the last attempt succeeded but the target was not ready yet.
+    pub pending: Option<Pending>,
+}
+
+impl TargetFailure {
+    pub fn reason(&self) -> Option<String> {
+        self.pending
+            .map(|pending| pending.to_string())
+            .or_else(|| self.last_error.as_ref().map(ToString::to_string))
+    }
+}
+
+impl fmt::Display for TargetFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = if self.attempts == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} ({} attempt{plural} in {}",
+            self.target,
+            self.attempts,
+            human::duration(self.elapsed)
+        )?;
+        match (&self.pending, &self.last_error) {
+            (Some(pending), _) => write!(f, ", {pending}")?,
+            (None, Some(e)) => write!(f, ", last error: {e}")?,
+            (None, None) => {}
+        }
+        f.write_str(")")
+    }
+}
+
+fn join_failures(failures: &[TargetFailure]) -> String {
+    failures
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DurationBounds {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+pub fn parse_duration(s: &str, label: &str, bounds: DurationBounds) -> Result<Duration> {
+    let duration = s
+        .parse::<humantime::Duration>()
+        .map_err(|e| Error::Config(format!("Invalid {label} '{s}': {e}")))?;
+    bounds.check(duration.into(), label)
+}
+
+impl DurationBounds {
+    pub const TIMEOUT: Self = Self {
+        min: Duration::from_millis(1),
+        max: Duration::from_secs(24 * 60 * 60),
+    };
+    pub const INTERVAL: Self = Self {
+        min: Duration::from_millis(1),
+        max: Duration::from_secs(60 * 60),
+    };
+    pub const LINGER: Self = Self {
+        min: Duration::ZERO,
+        max: Duration::from_secs(60 * 60),
+    };
+
+    pub fn check(self, duration: Duration, label: &str) -> Result<Duration> {
+        if duration < self.min || duration > self.max {
+            return Err(Error::Config(format!(
+                "Invalid {label} '{}': must be between {} and {}",
+                humantime::format_duration(duration),
+                humantime::format_duration(self.min),
+                humantime::format_duration(self.max)
+            )));
+        }
+        Ok(duration)
+    }
+}
+
+pub const DEFAULT_GRAPHQL_QUERY: &str = "{ __typename }";
+
+pub const DEFAULT_USER_AGENT: &str = concat!("waitup/", env!("CARGO_PKG_VERSION"));
+
 pub type Header = (String, String);
 pub type Headers = Vec<Header>;
 
+// A header name and, optionally, the exact value it must have. Values are
+// compared literally after trimming whitespace; there is no pattern syntax.
+pub type ExpectedHeader = (String, Option<String>);
+pub type BasicAuth = (String, Option<String>);
+
+// Only the source is kept; the token itself is read for each request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BearerToken {
+    Env(String),
+    File(PathBuf),
+}
+
+impl BearerToken {
+    pub fn read(&self) -> Result<String> {
+        let token = match self {
+            Self::Env(name) => std::env::var(name)
+                .map_err(|_| Error::Config(format!("Bearer token variable '{name}' is not set")))?,
+            // The file may be written or rotated while we wait.
+            Self::File(path) => std::fs::read_to_string(path).map_err(|e| {
+                Error::Connection(format!(
+                    "Cannot read bearer token file '{}': {e}",
+                    path.display()
+                ))
+            })?,
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(Error::Config(format!("Bearer token from {self} is empty")));
+        }
+        Ok(token.to_string())
+    }
+}
+
+impl fmt::Display for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env(name) => write!(f, "${name}"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+pub type Tag = (String, String);
+pub type Tags = Vec<Tag>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpOptions {
+    pub headers: Headers,
+    pub expect_headers: Vec<ExpectedHeader>,
+    pub max_response_time: Option<Duration>,
+    pub min_content_length: Option<usize>,
+    pub expect_json: Option<String>,
+    pub expect_sha256: Option<String>,
+    pub head_first: bool,
+    pub unix_socket: Option<PathBuf>,
+    pub basic_auth: Option<BasicAuth>,
+    pub bearer_token: Option<BearerToken>,
+    pub graphql_query: Option<String>,
+    pub cert_min_validity: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DnsFailure {
+    #[default]
+    Retry,
+    Fail,
+}
+
+// Caps probes in flight across every target sharing this config.
 #[derive(Debug, Clone)]
-pub enum Target {
-    Tcp { host: String, port: u16 },
-    Http { url: Url, headers: Headers },
+pub struct ProbeLimit {
+    pub max: u32,
+    pub permits: Arc<Semaphore>,
+}
+
+impl ProbeLimit {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max,
+            permits: Arc::new(Semaphore::new(max as usize)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    pub threshold: u32,
+    pub cooldown: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    Retry,
+    Abort,
+}
+
+impl RetryDecision {
+    pub fn classify(error: &Error, dns_failure: DnsFailure) -> Self {
+        match error {
+            Error::Dns(_) if dns_failure == DnsFailure::Fail => Self::Abort,
+            e if e.is_retryable() => Self::Retry,
+            _ => Self::Abort,
+        }
+    }
+}
+
+type RetryFn = dyn Fn(&Error) -> RetryDecision + Send + Sync;
+
+#[derive(Clone)]
+pub struct RetryHook(Arc<RetryFn>);
+
+impl RetryHook {
+    pub fn new(hook: impl Fn(&Error) -> RetryDecision + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+}
+
+impl fmt::Debug for RetryHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RetryHook(..)")
+    }
+}
+
+// Hooks for showing progress; every method defaults to doing nothing.
+pub trait ProgressReporter: Send + Sync {
+    fn target_started(&self, _target: &Target) {}
+
+    fn attempt_failed(&self, _target: &Target, _attempt: u32, _error: &Error) {}
+
+    fn target_finished(
+        &self,
+        _target: &Target,
+        _elapsed: Duration,
+        _attempts: u32,
+        _result: &Result<()>,
+    ) {
+    }
+
+    fn all_done(&self, _result: &WaitResult) {}
+}
+
+#[derive(Clone)]
+pub struct Reporter(Arc<dyn ProgressReporter>);
+
+impl Reporter {
+    pub fn new(reporter: impl ProgressReporter + 'static) -> Self {
+        Self(Arc::new(reporter))
+    }
+}
+
+impl core::ops::Deref for Reporter {
+    type Target = dyn ProgressReporter;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Reporter(..)")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartTls {
+    Smtp,
+    Imap,
+    Postgres,
+}
+
+impl StartTls {
+    fn parse(proto: &str) -> Option<Self> {
+        match proto {
+            "smtp" => Some(Self::Smtp),
+            "imap" => Some(Self::Imap),
+            "postgres" | "postgresql" => Some(Self::Postgres),
+            _ => None,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Smtp => "smtp",
+            Self::Imap => "imap",
+            Self::Postgres => "postgres",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    pub starttls: Option<StartTls>,
+    pub server_name: Option<String>,
+    pub alpn: Option<String>,
+    pub cert_min_validity: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpOptions {
+    pub nodelay: bool,
+    pub keepalive: Option<Duration>,
+    pub linger: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileOptions {
+    pub non_empty: bool,
+    pub contains: Option<String>,
+}
+
+#[cfg(feature = "consul")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsulOptions {
+    pub addr: Option<Url>,
+    pub token: Option<String>,
+    pub min_healthy: u32,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct S3Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+impl fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct S3Options {
+    pub endpoint: Option<Url>,
+    pub region: Option<String>,
+    pub credentials: Option<S3Credentials>,
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct LdapOptions {
+    pub bind_dn: Option<String>,
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for LdapOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LdapOptions")
+            .field("bind_dn", &self.bind_dn)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NatsOptions {
+    pub ping: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VaultOptions {
+    pub standby_ok: bool,
+    pub sealed_ok: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessOptions {
+    pub min_age: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessSelector {
+    Name(String),
+    PidFile(PathBuf),
+}
+
+impl fmt::Display for ProcessSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "process://{name}"),
+            Self::PidFile(path) => write!(f, "pidfile://{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TargetOptions {
+    pub auto_http: bool,
+    pub tcp: TcpOptions,
+    pub http: HttpOptions,
+    pub tls: TlsOptions,
+    pub file: FileOptions,
+    pub process: ProcessOptions,
+    pub vault: VaultOptions,
+    pub s3: S3Options,
+    pub ldap: LdapOptions,
+    pub nats: NatsOptions,
+    #[cfg(feature = "consul")]
+    pub consul: ConsulOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetKind {
+    Tcp {
+        host: Hostname,
+        port: Port,
+        options: TcpOptions,
+    },
+    Addr {
+        addr: SocketAddr,
+        options: TcpOptions,
+    },
+    Tls {
+        host: Hostname,
+        port: Port,
+        options: TlsOptions,
+    },
+    Http {
+        url: Url,
+        options: Box<HttpOptions>,
+    },
+    Pipe {
+        path: String,
+    },
+    File {
+        path: PathBuf,
+        options: FileOptions,
+    },
+    Mount {
+        path: PathBuf,
+        fstype: Option<String>,
+    },
+    Process {
+        selector: ProcessSelector,
+        options: ProcessOptions,
+    },
+    PortFree {
+        addr: SocketAddr,
+    },
+    Command {
+        argv: Vec<String>,
+    },
+    Etcd {
+        url: Url,
+        key: Option<String>,
+    },
+    Vault {
+        url: Url,
+        options: VaultOptions,
+    },
+    S3 {
+        bucket: Option<String>,
+        options: Box<S3Options>,
+    },
+    Nats {
+        host: Hostname,
+        port: Port,
+        tcp: TcpOptions,
+        options: NatsOptions,
+    },
+    Ldap {
+        host: Hostname,
+        port: Port,
+        tls: Option<TlsOptions>,
+        options: LdapOptions,
+    },
+    ZooKeeper {
+        host: Hostname,
+        port: Port,
+        options: TcpOptions,
+    },
+    #[cfg(feature = "consul")]
+    Consul {
+        service: String,
+        options: ConsulOptions,
+    },
+    Custom {
+        scheme: &'static str,
+        spec: String,
+        checker: Checker,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub kind: TargetKind,
+    pub tags: Tags,
 }
 
 impl Target {
-    pub fn parse(target_str: &str, headers: &[Header]) -> Result<Self> {
+    pub fn parse(target_str: &str, options: &TargetOptions) -> Result<Self> {
+        let (spec, tags) = split_tags(target_str)?;
+
+        if let Some(line) = spec.strip_prefix("exec:") {
+            return Ok(Self {
+                tags,
+                ..Self::command(split_words(line)?)?
+            });
+        }
+
+        if spec.starts_with("udp://") {
+            return Err(Error::Config(format!(
+                "UDP targets are not supported yet: '{target_str}'"
+            )));
+        }
+        if options.auto_http
+            && !spec.contains("://")
+            && let Some(url) = auto_http_url(spec)
+            && let Some(kind) = TargetKind::parse_scheme(&url, options)?
+        {
+            return Ok(Self { kind, tags });
+        }
+
+        let spec = spec
+            .strip_prefix("tcp://")
+            .map_or(spec, |s| s.strip_suffix('/').unwrap_or(s));
+
+        if let Ok(addr) = spec.parse::<SocketAddr>() {
+            if addr.port() == 0 {
+                return Err(Error::Config(format!(
+                    "Port must be 1-65535, got 0 in '{target_str}'"
+                )));
+            }
+            return Ok(Self {
+                kind: TargetKind::Addr {
+                    addr,
+                    options: options.tcp,
+                },
+                tags,
+            });
+        }
+
+        if let Some(kind) = TargetKind::parse_scheme(spec, options)? {
+            return Ok(Self { kind, tags });
+        }
+
+        let HostPort { host, port } = spec.parse()?;
+        Ok(Self::tcp_builder(host, port)
+            .nodelay(options.tcp.nodelay)
+            .keepalive(options.tcp.keepalive)
+            .linger(options.tcp.linger)
+            .tags(tags)
+            .build())
+    }
+
+    pub fn tcp_builder(host: Hostname, port: Port) -> TcpTargetBuilder {
+        TcpTargetBuilder {
+            host,
+            port,
+            options: TcpOptions::default(),
+            tags: Tags::new(),
+        }
+    }
+
+    pub fn command<I, S>(argv: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let argv: Vec<String> = argv.into_iter().map(Into::into).collect();
+        if argv.first().is_none_or(String::is_empty) {
+            return Err(Error::Config(
+                "Command target needs a program to run, e.g. exec:pg_isready -h db".to_string(),
+            ));
+        }
+        Ok(Self {
+            kind: TargetKind::Command { argv },
+            tags: Tags::new(),
+        })
+    }
+
+    pub fn from_socket_addr(addr: SocketAddr) -> Self {
+        Self {
+            kind: TargetKind::Addr {
+                addr,
+                options: TcpOptions::default(),
+            },
+            tags: Tags::new(),
+        }
+    }
+
+    pub fn matches(&self, selectors: &[Tag]) -> bool {
+        selectors
+            .iter()
+            .all(|(key, value)| self.tags.iter().any(|(k, v)| k == key && v == value))
+    }
+}
+
+// Bare host:443 and host:80 become HTTPS and HTTP checks of `/`.
+fn auto_http_url(spec: &str) -> Option<String> {
+    let HostPort { host, port } = spec.parse().ok()?;
+    match port {
+        Port::HTTPS => Some(format!("https://{host}/")),
+        Port::HTTP => Some(format!("http://{host}/")),
+        _ => None,
+    }
+}
+
+pub fn parse_tag(raw: &str) -> Result<Tag> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| Error::Config(format!("Invalid tag '{raw}': expected 'key=value'")))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(Error::Config(format!("Empty tag key in '{raw}'")));
+    }
+    Ok((key.to_string(), value.trim().to_string()))
+}
+
+impl From<SocketAddr> for Target {
+    fn from(addr: SocketAddr) -> Self {
+        Self::from_socket_addr(addr)
+    }
+}
+
+// Tags follow the last '#'. On URLs that is only taken as a tag list when
+// every piece is key=value, so a plain fragment stays part of the URL.
+fn split_tags(target_str: &str) -> Result<(&str, Tags)> {
+    let Some((spec, raw)) = target_str.rsplit_once('#') else {
+        return Ok((target_str, Tags::new()));
+    };
+    let is_tag_list = !raw.is_empty()
+        && raw.split(',').all(|part| {
+            part.split_once('=')
+                .is_some_and(|(key, _)| !key.trim().is_empty())
+        });
+    if spec.contains("://") && !is_tag_list {
+        return Ok((target_str, Tags::new()));
+    }
+    Ok((spec, parse_tags(raw, target_str)?))
+}
+
+fn parse_tags(raw: &str, target_str: &str) -> Result<Tags> {
+    if raw.is_empty() {
+        return Err(Error::Config(format!("Empty tag list in '{target_str}'")));
+    }
+    raw.split(',').map(parse_tag).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct TcpTargetBuilder {
+    host: Hostname,
+    port: Port,
+    options: TcpOptions,
+    tags: Tags,
+}
+
+impl TcpTargetBuilder {
+    pub const fn nodelay(mut self, nodelay: bool) -> Self {
+        self.options.nodelay = nodelay;
+        self
+    }
+
+    pub const fn keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.options.keepalive = keepalive;
+        self
+    }
+
+    pub const fn linger(mut self, linger: Option<Duration>) -> Self {
+        self.options.linger = linger;
+        self
+    }
+
+    pub fn tags(mut self, tags: Tags) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn build(self) -> Target {
+        Target {
+            kind: TargetKind::Tcp {
+                host: self.host,
+                port: self.port,
+                options: self.options,
+            },
+            tags: self.tags,
+        }
+    }
+}
+
+impl TargetKind {
+    fn parse_scheme(target_str: &str, options: &TargetOptions) -> Result<Option<Self>> {
+        if let Some(path) = parse_pipe_path(target_str)? {
+            return Ok(Some(Self::Pipe { path }));
+        }
+        if let Some(selector) = parse_process_selector(target_str)? {
+            return Ok(Some(Self::Process {
+                selector,
+                options: options.process,
+            }));
+        }
+        if let Some(service) = target_str.strip_prefix("consul://") {
+            let service = service.strip_suffix('/').unwrap_or(service);
+            if service.is_empty() || service.contains('/') {
+                return Err(Error::Config(format!(
+                    "Invalid Consul target '{target_str}': expected consul://<service>"
+                )));
+            }
+            #[cfg(feature = "consul")]
+            return Ok(Some(Self::Consul {
+                service: service.to_string(),
+                options: options.consul.clone(),
+            }));
+            #[cfg(not(feature = "consul"))]
+            return Err(Error::Config(format!(
+                "Consul targets need waitup built with the `consul` feature: '{target_str}'"
+            )));
+        }
+        if let Some((url, key)) = parse_service_url(target_str, "etcd", Port::ETCD)? {
+            return Ok(Some(Self::Etcd {
+                url,
+                key: (!key.is_empty()).then_some(key),
+            }));
+        }
+        if let Some((url, path)) = parse_service_url(target_str, "vault", Port::VAULT)? {
+            if !path.is_empty() {
+                return Err(Error::Config(format!(
+                    "Invalid vault target '{target_str}': expected vault://host[:port]"
+                )));
+            }
+            return Ok(Some(Self::Vault {
+                url,
+                options: options.vault,
+            }));
+        }
+        if let Some((rest, secure)) = target_str
+            .strip_prefix("ldap://")
+            .map(|rest| (rest, false))
+            .or_else(|| target_str.strip_prefix("ldaps://").map(|rest| (rest, true)))
+        {
+            let default_port = if secure { Port::LDAPS } else { Port::LDAP };
+            let HostPort { host, port } = HostPort::with_default_port(rest, default_port)?;
+            return Ok(Some(Self::Ldap {
+                host,
+                port,
+                tls: secure.then(|| options.tls.clone()),
+                options: options.ldap.clone(),
+            }));
+        }
+        if let Some(rest) = target_str.strip_prefix("nats://") {
+            let HostPort { host, port } = HostPort::with_default_port(rest, Port::NATS)?;
+            return Ok(Some(Self::Nats {
+                host,
+                port,
+                tcp: options.tcp,
+                options: options.nats,
+            }));
+        }
+        if let Some(bucket) = target_str.strip_prefix("s3://") {
+            let bucket = bucket.strip_suffix('/').unwrap_or(bucket);
+            let valid = bucket.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '.' | '_')
+            });
+            if !valid {
+                return Err(Error::Config(format!(
+                    "Invalid S3 bucket name in '{target_str}': expected s3://<bucket>"
+                )));
+            }
+            return Ok(Some(Self::S3 {
+                bucket: (!bucket.is_empty()).then(|| bucket.to_string()),
+                options: Box::new(options.s3.clone()),
+            }));
+        }
+        if let Some(rest) = target_str.strip_prefix("free://") {
+            let addr = match rest.parse::<u16>() {
+                Ok(port) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+                Err(_) => rest.parse().map_err(|_| {
+                    Error::Config(format!(
+                        "Invalid port in '{target_str}': expected free://<port> or free://<ip>:<port>"
+                    ))
+                })?,
+            };
+            // Binding port 0 always succeeds, so the target would be free at once.
+            if addr.port() == 0 {
+                return Err(Error::Config(format!(
+                    "Port must be 1-65535, got 0 in '{target_str}'"
+                )));
+            }
+            return Ok(Some(Self::PortFree { addr }));
+        }
+        if let Some(rest) = target_str.strip_prefix("mount://") {
+            let (path, fstype) = match rest.split_once("?fstype=") {
+                Some((path, fstype)) => (path, Some(fstype)),
+                None => (rest, None),
+            };
+            if path.is_empty() || fstype.is_some_and(str::is_empty) {
+                return Err(Error::Config(format!(
+                    "Missing path in '{target_str}': expected mount:///path/to/dir[?fstype=TYPE]"
+                )));
+            }
+            return Ok(Some(Self::Mount {
+                path: PathBuf::from(path),
+                fstype: fstype.map(str::to_string),
+            }));
+        }
+        if let Some(path) = target_str.strip_prefix("file://") {
+            if path.is_empty() {
+                return Err(Error::Config(format!(
+                    "Missing path in '{target_str}': expected file:///path/to/file"
+                )));
+            }
+            return Ok(Some(Self::File {
+                path: PathBuf::from(path),
+                options: options.file.clone(),
+            }));
+        }
+
+        let http = &options.http;
+        if let Some(url_str) = target_str.strip_prefix("graphql+") {
+            if let Some(Self::Http { url, mut options }) = Self::parse_scheme(url_str, options)? {
+                let query = http
+                    .graphql_query
+                    .as_deref()
+                    .unwrap_or(DEFAULT_GRAPHQL_QUERY);
+                options.graphql_query = Some(query.to_string());
+                return Ok(Some(Self::Http { url, options }));
+            }
+            return Err(Error::Config(format!(
+                "Invalid GraphQL target '{target_str}': expected graphql+http(s)://"
+            )));
+        }
+
         if target_str.starts_with("http://") || target_str.starts_with("https://") {
-            let url = Url::parse(target_str)
+            let mut url = Url::parse(target_str)
                 .map_err(|e| Error::Config(format!("Invalid URL '{target_str}': {e}")))?;
-            validate_headers(headers)?;
-            return Ok(Self::Http {
-                url,
-                headers: headers.to_vec(),
+            validate_headers(&http.headers)?;
+            for (key, _) in &http.expect_headers {
+                validate_header_name(key)?;
+            }
+            let mut options = Box::new(HttpOptions {
+                graphql_query: None,
+                ..http.clone()
             });
+            if let Some(auth) = take_userinfo(&mut url) {
+                options.basic_auth = Some(auth);
+            }
+            return Ok(Some(Self::Http { url, options }));
+        }
+
+        if let Some(addr) = target_str.strip_prefix("zk://") {
+            let HostPort { host, port } = addr.strip_suffix('/').unwrap_or(addr).parse()?;
+            return Ok(Some(Self::ZooKeeper {
+                host,
+                port,
+                options: options.tcp,
+            }));
+        }
+
+        if let Some(addr) = target_str.strip_prefix("tls://") {
+            let HostPort { host, port } = addr.parse()?;
+            return Ok(Some(Self::Tls {
+                host,
+                port,
+                options: options.tls.clone(),
+            }));
+        }
+
+        if let Some((proto, addr)) = target_str.split_once("+starttls://") {
+            let starttls = StartTls::parse(proto).ok_or_else(|| {
+                Error::Config(format!(
+                    "Unsupported STARTTLS protocol '{proto}': expected smtp, imap or postgres"
+                ))
+            })?;
+            let HostPort { host, port } = addr.parse()?;
+            return Ok(Some(Self::Tls {
+                host,
+                port,
+                options: TlsOptions {
+                    starttls: Some(starttls),
+                    ..options.tls.clone()
+                },
+            }));
         }
 
-        let (host, port_str) = target_str.split_once(':').ok_or_else(|| {
-            Error::Config(format!(
-                "Invalid target '{target_str}': expected host:port or URL"
-            ))
-        })?;
+        Ok(
+            checker::lookup(target_str).map(|(scheme, checker)| Self::Custom {
+                scheme,
+                spec: target_str.to_string(),
+                checker,
+            }),
+        )
+    }
+}
+
+fn parse_service_url(
+    target_str: &str,
+    scheme: &str,
+    default_port: Port,
+) -> Result<Option<(Url, String)>> {
+    let Some(rest) = target_str.strip_prefix(scheme) else {
+        return Ok(None);
+    };
+    let (http_scheme, rest) = if let Some(rest) = rest.strip_prefix("://") {
+        ("http", rest)
+    } else if let Some(rest) = rest.strip_prefix("s://") {
+        ("https", rest)
+    } else {
+        return Ok(None);
+    };
+
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let invalid = |reason: String| {
+        Error::Config(format!(
+            "Invalid {scheme} target '{target_str}': {reason} (expected {scheme}://host[:port][/...])"
+        ))
+    };
+    if authority.is_empty() {
+        return Err(invalid("missing host".to_string()));
+    }
+    let mut url =
+        Url::parse(&format!("{http_scheme}://{authority}/")).map_err(|e| invalid(e.to_string()))?;
+    if url.port().is_none() {
+        let _ = url.set_port(Some(default_port.get()));
+    }
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .map_err(|e| invalid(e.to_string()))?;
+    Ok(Some((url, path.into_owned())))
+}
+
+fn write_service_url(
+    f: &mut fmt::Formatter<'_>,
+    scheme: &str,
+    url: &Url,
+    default_port: Port,
+) -> fmt::Result {
+    let secure = if url.scheme() == "https" { "s" } else { "" };
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port().unwrap_or(default_port.get());
+    write!(f, "{scheme}{secure}://{host}:{port}")
+}
+
+fn parse_pipe_path(target_str: &str) -> Result<Option<String>> {
+    let path = match target_str.strip_prefix("npipe://") {
+        Some(rest) => rest.replace('/', "\\"),
+        None if target_str.starts_with(r"\\") => target_str.to_string(),
+        None => return Ok(None),
+    };
+
+    let valid = path
+        .strip_prefix(r"\\")
+        .and_then(|rest| rest.split_once('\\'))
+        .and_then(|(server, rest)| Some((server, rest.strip_prefix(r"pipe\")?)))
+        .is_some_and(|(server, name)| !server.is_empty() && !name.is_empty());
+    if !valid {
+        return Err(Error::Config(format!(
+            "Invalid named pipe '{target_str}': expected \\\\.\\pipe\\<name> or npipe:////./pipe/<name>"
+        )));
+    }
+    if !cfg!(windows) {
+        return Err(Error::Config(format!(
+            "Named pipe targets are only supported on Windows: '{target_str}'"
+        )));
+    }
+    Ok(Some(path))
+}
+
+fn parse_process_selector(target_str: &str) -> Result<Option<ProcessSelector>> {
+    let selector = if let Some(name) = target_str.strip_prefix("process://") {
+        ProcessSelector::Name(name.to_string())
+    } else if let Some(path) = target_str.strip_prefix("pidfile://") {
+        ProcessSelector::PidFile(PathBuf::from(path))
+    } else {
+        return Ok(None);
+    };
+
+    let empty = match &selector {
+        ProcessSelector::Name(name) => name.is_empty() || name.contains('/'),
+        ProcessSelector::PidFile(path) => path.as_os_str().is_empty(),
+    };
+    if empty {
+        return Err(Error::Config(format!(
+            "Invalid process target '{target_str}': expected process://<name> or pidfile:///path/to/file.pid"
+        )));
+    }
+    if !cfg!(target_os = "linux") {
+        return Err(Error::Config(format!(
+            "Process targets are only supported on Linux: '{target_str}'"
+        )));
+    }
+    Ok(Some(selector))
+}
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Hostname {
+    Name(String),
+    Ip(IpAddr),
+}
+
+impl Hostname {
+    pub fn new(host: &str) -> Result<Self> {
         if host.is_empty() {
-            return Err(Error::Config(format!("Empty hostname in '{target_str}'")));
+            return Err(Error::Config("Empty hostname".to_string()));
+        }
+        if let Ok(ip) = host.parse() {
+            return Ok(Self::Ip(ip));
+        }
+        // A fully qualified name may end in a dot; store it without one.
+        let name = host.strip_suffix('.').unwrap_or(host);
+        if name.len() > 253 {
+            return Err(Error::Config(format!("Hostname too long: '{host}'")));
+        }
+        for label in name.split('.') {
+            let valid = !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+            if !valid {
+                return Err(Error::Config(format!("Invalid hostname '{host}'")));
+            }
+        }
+        Ok(Self::Name(name.to_string()))
+    }
+
+    // Unlike Display, IPv6 literals come without brackets.
+    pub fn host_str(&self) -> Cow<'_, str> {
+        match self {
+            Self::Name(name) => Cow::Borrowed(name),
+            Self::Ip(ip) => Cow::Owned(ip.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Hostname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => f.write_str(name),
+            Self::Ip(IpAddr::V4(ip)) => ip.fmt(f),
+            Self::Ip(IpAddr::V6(ip)) => write!(f, "[{ip}]"),
         }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Port(NonZeroU16);
+
+impl Port {
+    pub const SSH: Self = Self::well_known(22);
+    pub const SMTP: Self = Self::well_known(25);
+    pub const DNS: Self = Self::well_known(53);
+    pub const HTTP: Self = Self::well_known(80);
+    pub const IMAP: Self = Self::well_known(143);
+    pub const LDAP: Self = Self::well_known(389);
+    pub const HTTPS: Self = Self::well_known(443);
+    pub const SUBMISSION: Self = Self::well_known(587);
+    pub const LDAPS: Self = Self::well_known(636);
+    pub const IMAPS: Self = Self::well_known(993);
+    pub const MSSQL: Self = Self::well_known(1433);
+    pub const ZOOKEEPER: Self = Self::well_known(2181);
+    pub const ETCD: Self = Self::well_known(2379);
+    pub const MYSQL: Self = Self::well_known(3306);
+    pub const NATS: Self = Self::well_known(4222);
+    pub const POSTGRES: Self = Self::well_known(5432);
+    pub const AMQP: Self = Self::well_known(5672);
+    pub const REDIS: Self = Self::well_known(6379);
+    pub const VAULT: Self = Self::well_known(8200);
+    pub const CONSUL: Self = Self::well_known(8500);
+    pub const KAFKA: Self = Self::well_known(9092);
+    pub const ELASTICSEARCH: Self = Self::well_known(9200);
+    pub const MEMCACHED: Self = Self::well_known(11211);
+    pub const MONGODB: Self = Self::well_known(27017);
+
+    const fn well_known(port: u16) -> Self {
+        match NonZeroU16::new(port) {
+            Some(port) => Self(port),
+            None => panic!("well-known port cannot be 0"),
+        }
+    }
+
+    pub fn new(port: u16) -> Result<Self> {
+        NonZeroU16::new(port)
+            .map(Self)
+            .ok_or_else(|| Error::Config("Port must be 1-65535, got 0".to_string()))
+    }
+
+    pub const fn get(self) -> u16 {
+        self.0.get()
+    }
+}
 
-        let port: u16 = port_str
+impl FromStr for Port {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(&(_, port)) = SERVICES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(port);
+        }
+        let port: u16 = s
             .parse()
-            .map_err(|_| Error::Config(format!("Invalid port '{port_str}' in '{target_str}'")))?;
+            .map_err(|_| Error::Config(format!("Invalid port '{s}'")))?;
+        Self::new(port)
+    }
+}
 
-        if port == 0 {
-            return Err(Error::Config(format!(
-                "Port must be 1-65535, got 0 in '{target_str}'"
-            )));
+// IANA service names plus common aliases, for targets like db:postgres.
+const SERVICES: &[(&str, Port)] = &[
+    ("ssh", Port::SSH),
+    ("smtp", Port::SMTP),
+    ("domain", Port::DNS),
+    ("dns", Port::DNS),
+    ("http", Port::HTTP),
+    ("imap", Port::IMAP),
+    ("ldap", Port::LDAP),
+    ("https", Port::HTTPS),
+    ("submission", Port::SUBMISSION),
+    ("ldaps", Port::LDAPS),
+    ("imaps", Port::IMAPS),
+    ("ms-sql-s", Port::MSSQL),
+    ("mssql", Port::MSSQL),
+    ("zookeeper", Port::ZOOKEEPER),
+    ("etcd-client", Port::ETCD),
+    ("etcd", Port::ETCD),
+    ("mysql", Port::MYSQL),
+    ("nats", Port::NATS),
+    ("postgresql", Port::POSTGRES),
+    ("postgres", Port::POSTGRES),
+    ("amqp", Port::AMQP),
+    ("redis", Port::REDIS),
+    ("vault", Port::VAULT),
+    ("consul", Port::CONSUL),
+    ("kafka", Port::KAFKA),
+    ("elasticsearch", Port::ELASTICSEARCH),
+    ("memcache", Port::MEMCACHED),
+    ("memcached", Port::MEMCACHED),
+    ("mongodb", Port::MONGODB),
+];
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HostPort {
+    pub host: Hostname,
+    pub port: Port,
+}
+
+impl HostPort {
+    fn with_default_port(s: &str, default_port: Port) -> Result<Self> {
+        let s = s.strip_suffix('/').unwrap_or(s);
+        s.parse().or_else(|_| {
+            let host = s.strip_prefix('[').and_then(|h| h.strip_suffix(']'));
+            Ok(Self {
+                host: Hostname::new(host.unwrap_or(s))
+                    .map_err(|e| Error::Config(format!("{e} in '{s}'")))?,
+                port: default_port,
+            })
+        })
+    }
+}
+
+impl FromStr for HostPort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (host, port) = match s.strip_prefix('[') {
+            Some(rest) => rest
+                .split_once("]:")
+                .filter(|(host, _)| host.parse::<Ipv6Addr>().is_ok()),
+            None => s.rsplit_once(':').filter(|(host, _)| !host.contains(':')),
         }
+        .ok_or_else(|| Error::Config(format!("Invalid target '{s}': expected host:port or URL")))?;
 
-        Ok(Self::Tcp {
-            host: host.to_string(),
-            port,
+        let in_target = |e: Error| Error::Config(format!("{e} in '{s}'"));
+        Ok(Self {
+            host: Hostname::new(host).map_err(in_target)?,
+            port: port.parse().map_err(in_target)?,
         })
     }
 }
 
+fn take_userinfo(url: &mut Url) -> Option<BasicAuth> {
+    if url.username().is_empty() && url.password().is_none() {
+        return None;
+    }
+    let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+    let auth = (decode(url.username()), url.password().map(decode));
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    Some(auth)
+}
+
 fn validate_headers(headers: &[Header]) -> Result<()> {
     for (key, value) in headers {
         if key.is_empty() {
@@ -74,29 +1324,537 @@ fn validate_headers(headers: &[Header]) -> Result<()> {
                 "HTTP header value cannot be empty".to_string(),
             ));
         }
-        if !key
+        validate_header_name(key)?;
+    }
+    Ok(())
+}
+
+fn validate_header_name(key: &str) -> Result<()> {
+    if key.is_empty()
+        || !key
             .chars()
             .all(|c| c.is_ascii_alphanumeric() || "-_".contains(c))
-        {
-            return Err(Error::Config(format!("Invalid HTTP header name: {key}")));
-        }
+    {
+        return Err(Error::Config(format!("Invalid HTTP header name: {key}")));
     }
     Ok(())
 }
 
-impl fmt::Display for Target {
+impl TargetKind {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Tcp { .. } | Self::Addr { .. } => "tcp",
+            Self::Tls { options, .. } if options.starttls.is_some() => "starttls",
+            Self::Tls { .. } => "tls",
+            Self::Http { options, .. } if options.graphql_query.is_some() => "graphql",
+            Self::Http { .. } => "http",
+            Self::Pipe { .. } => "npipe",
+            Self::File { .. } => "file",
+            Self::Mount { .. } => "mount",
+            Self::Process { .. } => "process",
+            Self::PortFree { .. } => "free",
+            Self::Command { .. } => "exec",
+            Self::Etcd { .. } => "etcd",
+            Self::Vault { .. } => "vault",
+            Self::S3 { .. } => "s3",
+            Self::Ldap { .. } => "ldap",
+            Self::Nats { .. } => "nats",
+            Self::ZooKeeper { .. } => "zookeeper",
+            #[cfg(feature = "consul")]
+            Self::Consul { .. } => "consul",
+            Self::Custom { scheme, .. } => scheme,
+        }
+    }
+}
+
+impl fmt::Display for TargetKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Tcp { host, port } => write!(f, "{host}:{port}"),
+            Self::Tcp { host, port, .. } => write!(f, "{host}:{port}"),
+            Self::Addr { addr, .. } => write!(f, "{addr}"),
+            Self::Tls {
+                host,
+                port,
+                options,
+            } => match options.starttls {
+                Some(proto) => write!(f, "{}+starttls://{host}:{port}", proto.as_str()),
+                None => write!(f, "tls://{host}:{port}"),
+            },
             Self::Http { url, .. } => write!(f, "{url}"),
+            Self::Pipe { path } => f.write_str(path),
+            Self::File { path, .. } => write!(f, "file://{}", path.display()),
+            Self::Mount { path, fstype } => {
+                write!(f, "mount://{}", path.display())?;
+                match fstype {
+                    Some(fstype) => write!(f, "?fstype={fstype}"),
+                    None => Ok(()),
+                }
+            }
+            Self::Process { selector, .. } => selector.fmt(f),
+            Self::PortFree { addr } => write!(f, "free://{addr}"),
+            Self::Command { argv } => write!(f, "exec:{}", join_words(argv)),
+            Self::ZooKeeper { host, port, .. } => write!(f, "zk://{host}:{port}"),
+            Self::Etcd { url, key } => {
+                write_service_url(f, "etcd", url, Port::ETCD)?;
+                key.as_ref().map_or(Ok(()), |key| write!(f, "/{key}"))
+            }
+            Self::Vault { url, .. } => write_service_url(f, "vault", url, Port::VAULT),
+            Self::Nats { host, port, .. } => write!(f, "nats://{host}:{port}"),
+            Self::Ldap {
+                host, port, tls, ..
+            } => {
+                let secure = if tls.is_some() { "s" } else { "" };
+                write!(f, "ldap{secure}://{host}:{port}")
+            }
+            Self::S3 { bucket, .. } => write!(f, "s3://{}", bucket.as_deref().unwrap_or_default()),
+            #[cfg(feature = "consul")]
+            Self::Consul { service, .. } => write!(f, "consul://{service}"),
+            Self::Custom { spec, .. } => f.write_str(spec),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetResult {
+    pub target: Arc<Target>,
+    pub elapsed: Duration,
+    pub attempts: u32,
+    pub successes: u32,
+    pub error: Option<TargetError>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TargetState {
+    #[default]
+    Pending,
+    Probing,
+    Ready,
+    Failed,
+    Stopped,
+}
+
+impl TargetState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Probing => "probing",
+            Self::Ready => "ready",
+            Self::Failed => "failed",
+            Self::Stopped => "stopped",
         }
     }
 }
 
+// A target while the wait is still running, as seen by WaitHandle::status.
+#[derive(Debug, Clone)]
+pub struct TargetStatus {
+    pub target: Arc<Target>,
+    pub state: TargetState,
+    pub elapsed: Duration,
+    pub attempts: u32,
+    pub successes: u32,
+}
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum TargetError {
+    #[error(transparent)]
+    Failed(#[from] Error),
+    #[error("not checked: {0}")]
+    Cancelled(CancelReason),
+    // Probed, then abandoned once the wait was decided.
+    #[error("stopped: {0}")]
+    Stopped(CancelReason),
+}
+
+impl TargetError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Failed(e) => e.code(),
+            Self::Cancelled(_) | Self::Stopped(_) => "CANCELLED",
+        }
+    }
+
+    pub const fn cancel_reason(&self) -> Option<CancelReason> {
+        match self {
+            Self::Failed(_) => None,
+            Self::Cancelled(reason) | Self::Stopped(reason) => Some(*reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    AnyReady,
+    DependencyFailed,
+    Deadline,
+}
+
+impl CancelReason {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::AnyReady => "any_ready",
+            Self::DependencyFailed => "dependency_failed",
+            Self::Deadline => "deadline",
+        }
+    }
+}
+
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::AnyReady => "enough other targets became ready",
+            Self::DependencyFailed => "an earlier target failed",
+            Self::Deadline => "the deadline passed",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitOutcome {
+    Ready,
+    TimedOut,
+    Cancelled,
+    RetriesExhausted,
+    FatalError,
+}
+
+impl WaitOutcome {
+    pub fn of(error: Option<&Error>) -> Self {
+        match error {
+            None => Self::Ready,
+            Some(Error::Timeout { .. }) => Self::TimedOut,
+            Some(Error::Exhausted(_)) => Self::RetriesExhausted,
+            Some(Error::Phase { source, .. }) => Self::of(Some(source)),
+            Some(_) => Self::FatalError,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ready => "ready",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+            Self::RetriesExhausted => "retries_exhausted",
+            Self::FatalError => "fatal_error",
+        }
+    }
+}
+
+impl TargetResult {
+    pub fn outcome(&self) -> WaitOutcome {
+        match &self.error {
+            None => WaitOutcome::Ready,
+            Some(TargetError::Cancelled(_) | TargetError::Stopped(_)) => WaitOutcome::Cancelled,
+            Some(TargetError::Failed(e)) => WaitOutcome::of(Some(e)),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.outcome() == WaitOutcome::Ready
+    }
+}
+
+pub trait TargetResultSliceExt {
+    fn partition_by_success(&self) -> (Vec<&TargetResult>, Vec<&TargetResult>);
+    fn group_by_kind(&self) -> BTreeMap<&'static str, Vec<&TargetResult>>;
+    fn group_by_tag(&self, key: &str) -> BTreeMap<&str, Vec<&TargetResult>>;
+}
+
+impl TargetResultSliceExt for [TargetResult] {
+    fn partition_by_success(&self) -> (Vec<&TargetResult>, Vec<&TargetResult>) {
+        self.iter().partition(|r| r.is_success())
+    }
+
+    fn group_by_kind(&self) -> BTreeMap<&'static str, Vec<&TargetResult>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for result in self {
+            groups
+                .entry(result.target.kind.name())
+                .or_default()
+                .push(result);
+        }
+        groups
+    }
+
+    fn group_by_tag(&self, key: &str) -> BTreeMap<&str, Vec<&TargetResult>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for result in self {
+            for (_, value) in result.target.tags.iter().filter(|(k, _)| k == key) {
+                groups.entry(value.as_str()).or_default().push(result);
+            }
+        }
+        groups
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quorum {
+    AllOf,
+    AnyOf,
+    KOfN(usize),
+}
+
+impl Quorum {
+    pub fn needed(self, total: usize) -> usize {
+        match self {
+            Self::AllOf => total,
+            Self::AnyOf => total.min(1),
+            Self::KOfN(k) => total.min(k),
+        }
+    }
+}
+
+impl FromStr for Quorum {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "all" => Ok(Self::AllOf),
+            "any" => Ok(Self::AnyOf),
+            k => match k.parse() {
+                Ok(0) | Err(_) => Err(Error::Config(format!(
+                    "Invalid quorum '{s}': expected all, any or a number of targets"
+                ))),
+                Ok(k) => Ok(Self::KOfN(k)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Quorum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AllOf => f.write_str("all"),
+            Self::AnyOf => f.write_str("any"),
+            Self::KOfN(k) => write!(f, "{k}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetGroup {
+    pub name: String,
+    pub targets: Vec<Arc<Target>>,
+    pub quorum: Quorum,
+}
+
+impl TargetGroup {
+    pub fn new(name: impl Into<String>, quorum: Quorum) -> Self {
+        Self {
+            name: name.into(),
+            targets: Vec::new(),
+            quorum,
+        }
+    }
+}
+
+impl FromIterator<Target> for TargetGroup {
+    fn from_iter<I: IntoIterator<Item = Target>>(iter: I) -> Self {
+        Self {
+            targets: iter.into_iter().map(Arc::new).collect(),
+            ..Self::new("targets", Quorum::AllOf)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WaitResult {
+    pub targets: Vec<TargetResult>,
+    pub elapsed: Duration,
+    pub error: Option<Error>,
+}
+
+impl WaitResult {
+    pub fn outcome(&self) -> WaitOutcome {
+        WaitOutcome::of(self.error.as_ref())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.outcome() == WaitOutcome::Ready
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WaitConfig {
-    pub timeout: Duration,
+    pub overall_timeout: Duration,
+    pub target_timeout: Option<Duration>,
     pub initial_interval: Duration,
-    pub wait_for_any: bool,
+    pub quorum: Quorum,
+    pub sequential: bool,
+    pub max_attempts: Option<u32>,
+    pub probe_limit: Option<ProbeLimit>,
+    pub initial_delay: Option<Duration>,
+    pub success_threshold: u32,
+    pub stable_for: Option<Duration>,
     pub connection_timeout: Duration,
+    pub adaptive_timeout: bool,
+    pub race_addresses: bool,
+    pub dns_failure: DnsFailure,
+    pub dns_interval: Option<Duration>,
+    pub dns_servers: Vec<SocketAddr>,
+    pub retry_hook: Option<RetryHook>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub deadline: Option<Instant>,
+    pub reporter: Option<Reporter>,
+    pub user_agent: String,
+    pub bind_address: Option<IpAddr>,
+    pub interface: Option<String>,
+    pub via: Option<String>,
+    pub http_client: Option<reqwest::Client>,
+    // While it reads true, no new attempts start; set by a WaitHandle.
+    pub pause: Option<watch::Receiver<bool>>,
+}
+
+// Matches the CLI's defaults.
+impl Default for WaitConfig {
+    fn default() -> Self {
+        Self {
+            overall_timeout: Duration::from_secs(30),
+            target_timeout: None,
+            initial_interval: Duration::from_secs(1),
+            quorum: Quorum::AllOf,
+            sequential: false,
+            max_attempts: None,
+            probe_limit: None,
+            initial_delay: None,
+            success_threshold: 1,
+            stable_for: None,
+            connection_timeout: Duration::from_secs(10),
+            adaptive_timeout: false,
+            race_addresses: false,
+            dns_failure: DnsFailure::default(),
+            dns_interval: None,
+            dns_servers: Vec::new(),
+            retry_hook: None,
+            circuit_breaker: None,
+            deadline: None,
+            reporter: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            bind_address: None,
+            interface: None,
+            via: None,
+            http_client: None,
+            pause: None,
+        }
+    }
+}
+
+impl WaitConfig {
+    pub fn deadline_from(&self, start: Instant) -> Instant {
+        let deadline = start + self.overall_timeout;
+        self.deadline.map_or(deadline, |d| d.min(deadline))
+    }
+
+    pub fn decide(&self, error: &Error) -> RetryDecision {
+        match &self.retry_hook {
+            Some(hook) => (hook.0)(error),
+            None => RetryDecision::classify(error, self.dns_failure),
+        }
+    }
+
+    pub fn retry_interval(&self, last_error: Option<&Error>, streak: u32) -> Duration {
+        let interval = match (last_error, self.dns_interval) {
+            (Some(Error::Dns(_)), Some(interval)) => interval,
+            _ => self.initial_interval,
+        };
+        match self.circuit_breaker {
+            Some(breaker) if streak >= breaker.threshold => interval.max(breaker.cooldown),
+            _ => interval,
+        }
+    }
+
+    pub fn target_deadline(&self, overall: Instant) -> Instant {
+        self.target_timeout
+            .map_or(overall, |t| overall.min(Instant::now() + t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(target: &str) -> Result<Target> {
+        Target::parse(target, &TargetOptions::default())
+    }
+
+    fn tags(pairs: &[(&str, &str)]) -> Tags {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn free_port_must_not_be_zero() {
+        assert_eq!(
+            parse("free://8080").unwrap().to_string(),
+            "free://0.0.0.0:8080"
+        );
+        assert_eq!(
+            parse("free://127.0.0.1:8080").unwrap().to_string(),
+            "free://127.0.0.1:8080"
+        );
+        for target in ["free://0", "free://127.0.0.1:0", "free://[::1]:0"] {
+            assert!(matches!(parse(target), Err(Error::Config(_))), "{target}");
+        }
+    }
+
+    #[test]
+    fn tags_on_host_port() {
+        let target = parse("db:5432#tier=core,team=payments").unwrap();
+        assert_eq!(target.tags, tags(&[("tier", "core"), ("team", "payments")]));
+        assert_eq!(target.to_string(), "db:5432");
+    }
+
+    #[test]
+    fn tags_on_url() {
+        let target = parse("http://localhost/health#tier=edge").unwrap();
+        assert_eq!(target.tags, tags(&[("tier", "edge")]));
+        let TargetKind::Http { url, .. } = &target.kind else {
+            panic!("expected an HTTP target");
+        };
+        assert_eq!(url.fragment(), None);
+    }
+
+    #[test]
+    fn url_fragment_is_not_a_tag() {
+        let target = parse("http://localhost/x#frag").unwrap();
+        assert!(target.tags.is_empty());
+        let TargetKind::Http { url, .. } = &target.kind else {
+            panic!("expected an HTTP target");
+        };
+        assert_eq!(url.fragment(), Some("frag"));
+    }
+
+    #[test]
+    fn tags_after_url_fragment() {
+        let target = parse("http://localhost/x#frag#tier=core").unwrap();
+        assert_eq!(target.tags, tags(&[("tier", "core")]));
+        let TargetKind::Http { url, .. } = &target.kind else {
+            panic!("expected an HTTP target");
+        };
+        assert_eq!(url.fragment(), Some("frag"));
+    }
+
+    #[test]
+    fn invalid_tags_on_host_port() {
+        assert!(parse("db:5432#tier").is_err());
+        assert!(parse("db:5432#").is_err());
+        assert!(parse("db:5432#=core").is_err());
+    }
+
+    #[test]
+    fn tag_values_are_trimmed() {
+        assert_eq!(
+            parse_tag(" tier = core ").unwrap(),
+            ("tier".to_string(), "core".to_string())
+        );
+        assert!(parse_tag("tier").is_err());
+    }
 }
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use reqwest::Url;
+
+use crate::connection::fetch_json;
+use crate::json::Value;
+use crate::types::{Error, Result, VaultOptions, WaitConfig};
+
+// Ask for 200 in every state so the body can be inspected instead of the status.
+const HEALTH_QUERY: &str = "standbyok=true&perfstandbyok=true&sealedcode=200&uninitcode=200\
+    &drsecondarycode=200&performancestandbycode=200";
+
+fn flag(health: &Value, name: &str) -> bool {
+    matches!(health.get(name), Some(Value::Bool(true)))
+}
+
+pub async fn probe_vault(
+    base: &Url,
+    options: VaultOptions,
+    config: &WaitConfig,
+    conn_timeout: Duration,
+) -> Result<()> {
+    let mut url = base
+        .join("v1/sys/health")
+        .map_err(|e| Error::Config(format!("Invalid Vault address {base}: {e}")))?;
+    url.set_query(Some(HEALTH_QUERY));
+
+    let health = fetch_json(&url, None, &[], config, conn_timeout).await?;
+    if health.get("initialized").is_none() || health.get("sealed").is_none() {
+        return Err(Error::Response(format!(
+            "Unexpected Vault health response from {base}"
+        )));
+    }
+
+    let state = if !flag(&health, "initialized") {
+        Some("not initialized").filter(|_| !options.sealed_ok)
+    } else if flag(&health, "sealed") {
+        Some("sealed").filter(|_| !options.sealed_ok)
+    } else if flag(&health, "standby") || flag(&health, "performance_standby") {
+        Some("a standby node").filter(|_| !options.standby_ok)
+    } else {
+        None
+    };
+    match state {
+        Some(state) => Err(Error::Response(format!("Vault at {base} is {state}"))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,62 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::connection::tcp_connect;
+use crate::dns::resolve;
+use crate::types::{Error, Hostname, Result, TcpOptions, WaitConfig};
+
+const MAX_REPLY: u64 = 256;
+
+pub async fn ruok(
+    host: &Hostname,
+    port: u16,
+    options: TcpOptions,
+    config: &WaitConfig,
+) -> Result<()> {
+    let addrs = resolve(host, port, &config.dns_servers).await?;
+    let mut stream = tcp_connect(&addrs, options, config).await?;
+    let io_error = |e: std::io::Error| Error::Connection(format!("ZooKeeper ruok failed: {e}"));
+
+    stream.write_all(b"ruok").await.map_err(io_error)?;
+    let mut reply = Vec::new();
+    (&mut stream)
+        .take(MAX_REPLY)
+        .read_to_end(&mut reply)
+        .await
+        .map_err(io_error)?;
+
+    check_reply(&String::from_utf8_lossy(&reply))
+}
+
+fn check_reply(reply: &str) -> Result<()> {
+    match reply.trim() {
+        "imok" => Ok(()),
+        "" => Err(Error::Response(
+            "ZooKeeper closed the connection without answering ruok".to_string(),
+        )),
+        // Since 3.5 four-letter words are off unless whitelisted; retrying
+        // cannot change that.
+        other if other.contains("not in the whitelist") => Err(Error::Config(format!(
+            "ZooKeeper refused ruok ('{other}'): add ruok to 4lw.commands.whitelist in zoo.cfg"
+        ))),
+        other => Err(Error::Response(format!(
+            "ZooKeeper answered '{other}' instead of 'imok'"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies() {
+        assert!(check_reply("imok").is_ok());
+        assert!(matches!(check_reply(""), Err(Error::Response(_))));
+        assert!(matches!(check_reply("busy"), Err(Error::Response(_))));
+        let refused = "ruok is not executed because it is not in the whitelist.\n";
+        let Err(Error::Config(message)) = check_reply(refused) else {
+            panic!("expected a config error");
+        };
+        assert!(message.contains("4lw.commands.whitelist"));
+    }
+}